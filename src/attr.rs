@@ -0,0 +1,73 @@
+//! Attribute macros, for timing whole functions.
+//!
+//! These live in their own module since an attribute can't share its name with the `timed!`
+//! macro at the crate root.
+//!
+//! # Examples
+//!
+//! `#[timed]` times every call to the function, and reports it like `timed!` does. The label is
+//! the path of the function, unless you give your own:
+//!
+//! ```
+//! # extern crate tid;
+//! use tid::attr::timed;
+//!
+//! #[timed]
+//! fn parse(s: &str) -> Result<i32, std::num::ParseIntError> {
+//!     let n = s.parse::<i32>()?; // early returns are timed as well.
+//!     Ok(n * 2)
+//! }
+//!
+//! #[timed("checking the input")]
+//! fn check(n: i32) -> bool {
+//!     n > 10
+//! }
+//! # fn main() {
+//! # assert!(check(parse("12").unwrap()));
+//! # }
+//! ```
+//!
+//! Methods get their type in the label as well, so they don't get mixed up with functions of the
+//! same name:
+//!
+//! ```
+//! # extern crate tid;
+//! use tid::attr::timed;
+//!
+//! struct Parser;
+//!
+//! impl Parser {
+//!     #[timed]
+//!     fn parse(&self, s: &str) -> usize {
+//!         s.len()
+//!     }
+//! }
+//!
+//! #[timed]
+//! fn parse(s: &str) -> usize {
+//!     s.len()
+//! }
+//! # fn main() {
+//! Parser.parse("abc");
+//! parse("abc");
+//! let labels = tid::registry::stats()
+//!     .into_iter()
+//!     .map(|s| s.label)
+//!     .collect::<Vec<_>>();
+//! # #[cfg(feature = "enabled")]
+//! assert!(labels.iter().any(|l| l.ends_with("::Parser::parse")));
+//! # #[cfg(feature = "enabled")]
+//! assert!(labels.iter().any(|l| l.ends_with("::parse") && !l.contains("Parser")));
+//! # }
+//! ```
+//!
+//! `async fn`s are timed from when they are first polled until they complete:
+//!
+//! ```edition2018
+//! #[tid::attr::timed]
+//! async fn fetch() -> Vec<u8> {
+//!     Vec::new()
+//! }
+//! ```
+
+pub use tid_macros::timed;
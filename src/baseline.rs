@@ -0,0 +1,406 @@
+//! Save timings to a file, and compare later runs against them. This needs the `serde` feature.
+//!
+//! A `Snapshot` has the `Report` of a `Timer` and the statistics for each label. It's saved as
+//! JSON with a version number, so that files from other versions of `tid` are recognized.
+//! Comparing two snapshots gives the change in mean time of each label, and whether the change is
+//! significant, from a rough t-test over the samples.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use tid::Timer;
+//! # use tid::baseline::Snapshot;
+//! # fn build_index() {  }
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let mut t = Timer::new();
+//! build_index();
+//! t.mark("build index");
+//!
+//! let current = Snapshot::from_timer(&t);
+//! let baseline = Snapshot::load("baseline.json")?;
+//! let comparison = current.compare(&baseline);
+//! comparison.present();
+//! comparison.check(5.0)?; // Fail if any label is more than 5% slower.
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! `present` prints something like
+//!
+//! ```text
+//! [diff] build index                  12.0412ms ->   14.1127ms   +2.0715ms  +17.2% *
+//! ```
+//!
+//! The mark at the end is `*` if the change is significant, `?` if there are too few samples to
+//! tell, and nothing if it isn't. Labels marked `?`, like ones timed only once per run, are
+//! compared by their times alone, so a single slow run is enough to fail `check`.
+//!
+//! With the `cli` feature there is also a `tid` binary, which can show, compare, merge and convert
+//! saved snapshots. Run `tid help` for the details.
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use format::{self, Format};
+use report::Report;
+use sink;
+use stats::Stats;
+use timer::Timer;
+
+/// The version of the file format written by `Snapshot::save`.
+pub const VERSION: u32 = 1;
+
+/// The timings of one run, as saved to a file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Always `VERSION` for snapshots made by this version of `tid`.
+    pub version: u32,
+    pub report: Report,
+    /// Statistics for each label. These include sections recorded into histograms, which are not
+    /// in the report.
+    pub stats: Vec<Stats>,
+}
+
+impl Snapshot {
+    /// Take a snapshot of the timings of `timer`.
+    pub fn from_timer(timer: &Timer) -> Snapshot {
+        Snapshot {
+            version: VERSION,
+            report: timer.report(),
+            stats: timer.stats(),
+        }
+    }
+
+    /// Take a snapshot of `report`.
+    pub fn from_report(report: Report) -> Snapshot {
+        Snapshot {
+            version: VERSION,
+            stats: report.stats(),
+            report,
+        }
+    }
+
+    /// Write the snapshot as JSON to `w`.
+    pub fn write<W: Write>(&self, w: W) -> io::Result<()> {
+        serde_json::to_writer(w, self).map_err(io::Error::from)
+    }
+
+    /// Read a snapshot written with `write`.
+    ///
+    /// Fails with `io::ErrorKind::InvalidData` if it's not a snapshot, or if it's written by a
+    /// version of `tid` with another format.
+    pub fn read<R: Read>(r: R) -> io::Result<Snapshot> {
+        let value: serde_json::Value = serde_json::from_reader(r).map_err(io::Error::from)?;
+        match value.get("version").and_then(|v| v.as_u64()) {
+            Some(v) if v == u64::from(VERSION) => {}
+            Some(v) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unsupported snapshot version {}, expected {}", v, VERSION),
+                ))
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "not a snapshot: no version",
+                ))
+            }
+        }
+        serde_json::from_value(value).map_err(io::Error::from)
+    }
+
+    /// Write the snapshot to the file at `path`, replacing it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+
+    /// Read a snapshot from the file at `path`. See `read`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Snapshot> {
+        Snapshot::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Compare `self` against `baseline`, label by label.
+    ///
+    /// ```
+    /// # use tid::{Entry, Report};
+    /// # use tid::baseline::{Significance, Snapshot};
+    /// # fn report(durations: &[u64]) -> Report {
+    /// #     let entries = durations.iter().enumerate().map(|(index, &duration_ns)| Entry {
+    /// #         label: "build index".to_string(), index, parent: None, depth: 0, start_ns: 0,
+    /// #         duration_ns, cpu: None, fields: Default::default(), throughput: None,
+    /// #         budget_ns: None,
+    /// #     }).collect();
+    /// #     Report { origin_ns: 0, thread: 1, total_ns: 0, entries }
+    /// # }
+    /// let baseline = Snapshot::from_report(report(&[100, 102, 98, 101, 99]));
+    /// let current = Snapshot::from_report(report(&[120, 121, 119, 122, 118]));
+    /// let comparison = current.compare(&baseline);
+    /// let change = &comparison.changes[0];
+    /// assert_eq!(change.percent(), Some(20.0));
+    /// assert_eq!(change.significance(), Significance::Yes);
+    /// assert!(comparison.check(5.0).is_err());
+    /// assert!(comparison.check(25.0).is_ok());
+    /// ```
+    pub fn compare(&self, baseline: &Snapshot) -> Comparison {
+        let mut changes = self
+            .stats
+            .iter()
+            .map(|s| Change {
+                label: s.label.clone(),
+                before: baseline.stats.iter().find(|b| b.label == s.label).cloned(),
+                after: Some(s.clone()),
+            })
+            .collect::<Vec<_>>();
+        for b in &baseline.stats {
+            if !self.stats.iter().any(|s| s.label == b.label) {
+                changes.push(Change {
+                    label: b.label.clone(),
+                    before: Some(b.clone()),
+                    after: None,
+                });
+            }
+        }
+        Comparison { changes }
+    }
+}
+
+/// Whether a change is more than noise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Significance {
+    Yes,
+    No,
+    /// There are too few samples to tell.
+    Unknown,
+}
+
+/// The change of a single label between two snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub label: String,
+    /// Statistics in the baseline, or `None` if the label is new.
+    pub before: Option<Stats>,
+    /// Statistics in the current run, or `None` if the label is gone.
+    pub after: Option<Stats>,
+}
+
+impl Change {
+    /// The change in mean time, in nanoseconds, if the label is in both snapshots.
+    pub fn delta_ns(&self) -> Option<f64> {
+        match (&self.before, &self.after) {
+            (Some(b), Some(a)) => Some(a.mean_ns - b.mean_ns),
+            _ => None,
+        }
+    }
+
+    /// The change in mean time, in percent of the baseline.
+    pub fn percent(&self) -> Option<f64> {
+        let before = self.before.as_ref()?.mean_ns;
+        let delta = self.delta_ns()?;
+        Some(if before == 0.0 {
+            0.0
+        } else {
+            delta / before * 100.0
+        })
+    }
+
+    /// Whether the change is significant, with Welch's t-test at 95% confidence. It's `Unknown` if
+    /// either side has fewer than two samples. With few samples only large changes are
+    /// significant:
+    ///
+    /// ```
+    /// # use tid::Stats;
+    /// # use tid::baseline::{Change, Significance};
+    /// let change = |before: &[u64], after: &[u64]| Change {
+    ///     label: "parse".to_string(),
+    ///     before: Some(Stats::from_samples("parse", before)),
+    ///     after: Some(Stats::from_samples("parse", after)),
+    /// };
+    /// assert_eq!(change(&[100, 101], &[110, 112]).significance(), Significance::No);
+    /// assert_eq!(
+    ///     change(&[100, 101, 99, 100], &[110, 112, 111, 110]).significance(),
+    ///     Significance::Yes
+    /// );
+    /// ```
+    pub fn significance(&self) -> Significance {
+        let (b, a) = match (&self.before, &self.after) {
+            (Some(b), Some(a)) if b.count >= 2 && a.count >= 2 => (b, a),
+            _ => return Significance::Unknown,
+        };
+        // `Stats` has the population variance, so correct it to the sample variance, and divide by
+        // the count for the variance of the mean.
+        let var = |s: &Stats| s.stddev_ns * s.stddev_ns / (s.count - 1) as f64;
+        let (vb, va) = (var(b), var(a));
+        let se = (vb + va).sqrt();
+        let delta = a.mean_ns - b.mean_ns;
+        let significant = if se == 0.0 {
+            delta != 0.0
+        } else {
+            // The Welch-Satterthwaite degrees of freedom.
+            let df = (vb + va) * (vb + va)
+                / (vb * vb / (b.count - 1) as f64 + va * va / (a.count - 1) as f64);
+            (delta / se).abs() > critical_t(df)
+        };
+        if significant {
+            Significance::Yes
+        } else {
+            Significance::No
+        }
+    }
+
+    /// Is the label slower by more than `threshold` percent, without the change being noise?
+    ///
+    /// If the significance is `Unknown`, because a side has a single sample, there's no telling
+    /// noise from a real change. Then the means are compared as they are, and any slowdown over
+    /// `threshold` is a regression.
+    ///
+    /// ```
+    /// # use tid::Stats;
+    /// # use tid::baseline::{Change, Significance};
+    /// let change = Change {
+    ///     label: "startup".to_string(),
+    ///     before: Some(Stats::from_samples("startup", &[100])),
+    ///     after: Some(Stats::from_samples("startup", &[110])),
+    /// };
+    /// assert_eq!(change.significance(), Significance::Unknown);
+    /// assert!(change.is_regression(5.0));
+    /// assert!(!change.is_regression(20.0));
+    /// ```
+    pub fn is_regression(&self, threshold: f64) -> bool {
+        self.percent().is_some_and(|p| p > threshold) && self.significance() != Significance::No
+    }
+}
+
+/// The two-sided 95% critical value of Student's t distribution with `df` degrees of freedom,
+/// rounded down to a row of the table, so it's on the strict side.
+fn critical_t(df: f64) -> f64 {
+    const TABLE: [(f64, f64); 16] = [
+        (1.0, 12.706),
+        (2.0, 4.303),
+        (3.0, 3.182),
+        (4.0, 2.776),
+        (5.0, 2.571),
+        (6.0, 2.447),
+        (7.0, 2.365),
+        (8.0, 2.306),
+        (9.0, 2.262),
+        (10.0, 2.228),
+        (12.0, 2.179),
+        (15.0, 2.131),
+        (20.0, 2.086),
+        (30.0, 2.042),
+        (60.0, 2.000),
+        (120.0, 1.980),
+    ];
+    if df > 1_000.0 {
+        return 1.960;
+    }
+    TABLE
+        .iter()
+        .rev()
+        .find(|&&(d, _)| d <= df)
+        .map_or(TABLE[0].1, |&(_, t)| t)
+}
+
+/// The result of `Snapshot::compare`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comparison {
+    /// One change for each label in either snapshot. Labels in the current run come first, in
+    /// order, followed by labels only in the baseline.
+    pub changes: Vec<Change>,
+}
+
+impl Comparison {
+    /// Format one line for each label.
+    pub fn lines(&self, format: &Format) -> Vec<String> {
+        let width = format.width_of(self.changes.iter().map(|c| &c.label[..]));
+        let mean = |s: &Option<Stats>| match *s {
+            Some(ref s) => format.duration(s.mean_ns.round() as u64),
+            None => format!("{:>w$}", "-", w = format.duration(0).chars().count()),
+        };
+        self.changes
+            .iter()
+            .map(|c| {
+                let mut line = format!(
+                    "\t[diff] {:<w$} {} -> {}",
+                    c.label,
+                    mean(&c.before),
+                    mean(&c.after),
+                    w = width
+                );
+                match (c.delta_ns(), c.percent()) {
+                    (Some(delta), Some(percent)) => {
+                        let sign = if delta < 0.0 { '-' } else { '+' };
+                        let mark = match c.significance() {
+                            Significance::Yes => " *",
+                            Significance::No => "",
+                            Significance::Unknown => " ?",
+                        };
+                        let delta = format.duration(delta.abs().round() as u64);
+                        line += &format!(
+                            " {:>w$} {:+6.1}%{}",
+                            format!("{}{}", sign, delta.trim_start()),
+                            percent,
+                            mark,
+                            w = delta.chars().count()
+                        );
+                    }
+                    _ if c.before.is_none() => line += " new",
+                    _ => line += " gone",
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Print the comparison with the global format and sink.
+    pub fn present(&self) {
+        let sink = sink::global();
+        for line in self.lines(&format::global()) {
+            sink.line(&line);
+        }
+    }
+
+    /// Fail if any label is a regression of more than `threshold` percent. See
+    /// `Change::is_regression`.
+    pub fn check(&self, threshold: f64) -> Result<(), Regression> {
+        let labels = self
+            .changes
+            .iter()
+            .filter(|c| c.is_regression(threshold))
+            .map(|c| c.label.clone())
+            .collect::<Vec<_>>();
+        if labels.is_empty() {
+            Ok(())
+        } else {
+            Err(Regression { threshold, labels })
+        }
+    }
+}
+
+/// The error from `Comparison::check`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    pub threshold: f64,
+    /// The labels which got slower.
+    pub labels: Vec<String>,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "slower by more than {}%: {}",
+            self.threshold,
+            self.labels.join(", ")
+        )
+    }
+}
+
+impl Error for Regression {}
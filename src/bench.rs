@@ -0,0 +1,150 @@
+//! Small benchmarks, for when a full benchmarking framework is too much.
+//!
+//! `Bench::run` first runs a closure for a warm-up period, which also gives an estimate of how
+//! long a single run takes. Then it takes a number of samples, each timing a batch of runs, with
+//! the batch size chosen so that all samples together take about the measurement time. The time of
+//! each sample is divided by the batch size, and the statistics over all samples are printed like
+//! `Timer::present` prints them, and returned.
+//!
+//! The return value of the closure is passed through `black_box`, so the work isn't optimized
+//! away. Benchmarks work from `main` as well as from plain `#[test]`s, but remember to run them
+//! with `--release`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use tid::bench::{self, Bench};
+//! let v = (0..1000).collect::<Vec<u64>>();
+//! let stats = Bench::new()
+//!     .warmup(Duration::from_millis(10))
+//!     .time(Duration::from_millis(50))
+//!     .run("sum", || v.iter().sum::<u64>());
+//! assert_eq!(stats.count, 50);
+//!
+//! // With the default warm-up and measurement times.
+//! # if false {
+//! bench::run("product", || v.iter().product::<u64>());
+//! # }
+//! ```
+//!
+//! which prints something like
+//!
+//! ```text
+//! [bench] sum 50 samples of 1818 runs
+//! [timer] sum                        n=50 total    0.0142ms min    0.0003ms median    0.0003ms mean    0.0003ms sd    0.0000ms p90    0.0003ms p99    0.0003ms max    0.0004ms
+//! ```
+use std::hint::black_box;
+use std::slice;
+use std::sync::Arc;
+use std::time::Duration;
+
+use clock::{Clock, Monotonic};
+use format::{self, Format};
+use sink::{self, Sink};
+use stats::Stats;
+
+/// Settings for running benchmarks. See the module documentation.
+pub struct Bench {
+    warmup_ns: u64,
+    time_ns: u64,
+    samples: usize,
+    sink: Option<Arc<dyn Sink>>,
+    format: Option<Format>,
+}
+
+impl Default for Bench {
+    fn default() -> Self {
+        Self {
+            warmup_ns: 500_000_000,
+            time_ns: 2_000_000_000,
+            samples: 50,
+            sink: None,
+            format: None,
+        }
+    }
+}
+
+/// Run a benchmark with the default settings. See `Bench::run`.
+pub fn run<F: FnMut() -> R, R>(label: &str, f: F) -> Stats {
+    Bench::new().run(label, f)
+}
+
+impl Bench {
+    /// The default settings: half a second of warm-up, and 50 samples in about two seconds.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Run the closure for `warmup` before measuring.
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup_ns = ::nanos(warmup);
+        self
+    }
+
+    /// Measure for about `time` in total.
+    pub fn time(mut self, time: Duration) -> Self {
+        self.time_ns = ::nanos(time);
+        self
+    }
+
+    /// Take `samples` samples.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `samples` is 0.
+    pub fn samples(mut self, samples: usize) -> Self {
+        assert!(samples > 0, "`samples` must be at least 1");
+        self.samples = samples;
+        self
+    }
+
+    /// Print to `sink`, instead of the one set with `tid::set_sink`.
+    pub fn sink<S: Sink + 'static>(mut self, sink: S) -> Self {
+        self.sink = Some(Arc::new(sink));
+        self
+    }
+
+    /// Print with `format`, instead of the one set with `tid::set_format`.
+    pub fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Benchmark `f`, and print and return the statistics of the time per run.
+    pub fn run<F: FnMut() -> R, R>(&self, label: &str, mut f: F) -> Stats {
+        let clock = Monotonic;
+
+        let start = clock.now();
+        let mut runs = 0u64;
+        let mut elapsed = 0;
+        while runs == 0 || elapsed < self.warmup_ns {
+            black_box(f());
+            runs += 1;
+            elapsed = clock.now() - start;
+        }
+        let per_run = (elapsed / runs).max(1);
+        let batch = (self.time_ns / self.samples as u64 / per_run).max(1);
+
+        let mut samples = Vec::with_capacity(self.samples);
+        for _ in 0..self.samples {
+            let t0 = clock.now();
+            for _ in 0..batch {
+                black_box(f());
+            }
+            samples.push((clock.now() - t0) / batch);
+        }
+        let stats = Stats::from_samples(label, &samples);
+
+        let sink = self.sink.clone().unwrap_or_else(sink::global);
+        let format = self.format.clone().unwrap_or_else(format::global);
+        sink.line(&format!(
+            "[bench] {} {} samples of {} runs",
+            label, self.samples, batch
+        ));
+        for line in format.stats(slice::from_ref(&stats)) {
+            sink.line(&line);
+        }
+        stats
+    }
+}
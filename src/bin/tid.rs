@@ -0,0 +1,530 @@
+//! Command line tool for timing files written by `tid`.
+//!
+//! Reads snapshots (`baseline::Snapshot::save`), reports (`Report::write_json`), CSV and TSV files
+//! and Chrome traces. Only snapshots and reports keep all information; the others are read as
+//! flat lists of sections, with one report for each thread. Several reports are written to JSON
+//! as a list of snapshots.
+extern crate serde_json;
+extern crate tid;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use tid::baseline::{self, Snapshot};
+use tid::chrome::Trace;
+use tid::csv::{self, Separator};
+use tid::format::Unit;
+use tid::{Entry, Fields, Format, Report, Stats, Value};
+
+const USAGE: &str = "\
+usage: tid <command> [options]
+
+commands:
+    show <file>                          print the sections of a report
+    diff <baseline> <current> [--threshold <percent>]
+                                         compare two reports, and fail if a label is slower by
+                                         more than <percent>
+    merge <file>... [--output <file>]    print statistics over all reports, and save them as
+                                         JSON, CSV or TSV, by the extension of <file>
+    convert <input> <output> [--to json|csv|tsv|chrome]
+                                         convert between formats; the output format is taken
+                                         from the extension of <output> unless given
+
+Files are JSON snapshots or reports, CSV or TSV, or Chrome traces.";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let result = match args.first().map(|a| &a[..]) {
+        Some("show") => show(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some("merge") => merge(&args[1..]),
+        Some("convert") => convert(&args[1..]),
+        Some("help") | Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        _ => Err(Error::Usage),
+    };
+    match result {
+        Ok(code) => process::exit(code),
+        Err(Error::Usage) => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+        Err(Error::Io(e)) => {
+            eprintln!("tid: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
+enum Error {
+    Usage,
+    Io(io::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+/// Split `args` into positional arguments and `--name value` options.
+fn parse<'a>(
+    args: &'a [String],
+    options: &[&str],
+) -> Result<(Vec<&'a str>, HashMap<String, &'a str>), Error> {
+    let mut positional = Vec::new();
+    let mut values = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(name) = arg.strip_prefix("--") {
+            if !options.contains(&name) {
+                return Err(Error::Usage);
+            }
+            let value = args.next().ok_or(Error::Usage)?;
+            values.insert(name.to_string(), &value[..]);
+        } else {
+            positional.push(&arg[..]);
+        }
+    }
+    Ok((positional, values))
+}
+
+fn show(args: &[String]) -> Result<i32, Error> {
+    let (files, _) = parse(args, &[])?;
+    if files.len() != 1 {
+        return Err(Error::Usage);
+    }
+    let snapshots = read(files[0])?;
+    let format = format();
+    for snapshot in &snapshots {
+        if snapshots.len() > 1 {
+            println!("[thread {}]", snapshot.report.thread);
+        }
+        let lines = if snapshot.report.entries.is_empty() {
+            format.stats(&snapshot.stats)
+        } else {
+            format.report(&snapshot.report)
+        };
+        print(&lines);
+    }
+    Ok(0)
+}
+
+fn diff(args: &[String]) -> Result<i32, Error> {
+    let (files, options) = parse(args, &["threshold"])?;
+    if files.len() != 2 {
+        return Err(Error::Usage);
+    }
+    let threshold = match options.get("threshold") {
+        Some(t) => Some(t.parse::<f64>().map_err(|_| Error::Usage)?),
+        None => None,
+    };
+    let baseline = combine(read(files[0])?);
+    let current = combine(read(files[1])?);
+    let comparison = current.compare(&baseline);
+    print(&comparison.lines(&format()));
+    if let Some(threshold) = threshold {
+        if let Err(e) = comparison.check(threshold) {
+            eprintln!("tid: {}", e);
+            return Ok(1);
+        }
+    }
+    Ok(0)
+}
+
+fn merge(args: &[String]) -> Result<i32, Error> {
+    let (files, options) = parse(args, &["output"])?;
+    if files.is_empty() {
+        return Err(Error::Usage);
+    }
+    let mut snapshots = Vec::new();
+    for file in files {
+        snapshots.extend(read(file)?);
+    }
+    let merged = combine(snapshots);
+    print(&format().stats(&merged.stats));
+    if let Some(output) = options.get("output") {
+        write_stats(&merged, output)?;
+    }
+    Ok(0)
+}
+
+/// The statistics of all `snapshots` added up, label by label, in a snapshot without sections.
+/// A single snapshot is kept as it is. The percentiles of a label are exact if all its samples
+/// are in the reports, and estimated otherwise, see `Stats::merge`.
+fn combine(mut snapshots: Vec<Snapshot>) -> Snapshot {
+    if snapshots.len() == 1 {
+        return snapshots.remove(0);
+    }
+    let mut stats: Vec<Stats> = Vec::new();
+    let mut samples: HashMap<String, Option<Vec<u64>>> = HashMap::new();
+    for snapshot in &snapshots {
+        for s in &snapshot.stats {
+            let durations = snapshot
+                .report
+                .entries
+                .iter()
+                .filter(|e| e.label == s.label)
+                .map(|e| e.duration_ns)
+                .collect::<Vec<_>>();
+            // Sections recorded into histograms are only in the statistics.
+            let complete = durations.len() as u64 == s.count;
+            let all = samples
+                .entry(s.label.clone())
+                .or_insert_with(|| Some(Vec::new()));
+            match *all {
+                Some(ref mut kept) if complete => kept.extend(durations),
+                _ => *all = None,
+            }
+            match stats.iter_mut().find(|m| m.label == s.label) {
+                Some(m) => m.merge(s),
+                None => stats.push(s.clone()),
+            }
+        }
+    }
+    for s in &mut stats {
+        if let Some(Some(samples)) = samples.get(&s.label) {
+            let exact = Stats::from_samples(&s.label, samples);
+            s.median_ns = exact.median_ns;
+            s.p90_ns = exact.p90_ns;
+            s.p99_ns = exact.p99_ns;
+        }
+    }
+    Snapshot {
+        version: baseline::VERSION,
+        report: Report {
+            origin_ns: 0,
+            thread: 0,
+            total_ns: 0,
+            entries: Vec::new(),
+        },
+        stats,
+    }
+}
+
+fn convert(args: &[String]) -> Result<i32, Error> {
+    let (files, options) = parse(args, &["to"])?;
+    if files.len() != 2 {
+        return Err(Error::Usage);
+    }
+    let snapshots = read(files[0])?;
+    write(&snapshots, files[1], options.get("to").cloned())?;
+    Ok(0)
+}
+
+/// The format of everything printed, with units to fit the times, as saved timings can be of
+/// anything from nanoseconds to minutes.
+fn format() -> Format {
+    Format::new().unit(Unit::Auto)
+}
+
+fn print(lines: &[String]) {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in lines {
+        let _ = writeln!(out, "{}", line);
+    }
+}
+
+/// Read the snapshots in a snapshot, a report, a Chrome trace or a CSV file.
+fn read(path: &str) -> io::Result<Vec<Snapshot>> {
+    read_file(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+fn read_file(path: &str) -> io::Result<Vec<Snapshot>> {
+    let text = fs::read_to_string(path)?;
+    let snapshots = if text.trim_start().starts_with(['{', '[']) {
+        let value: serde_json::Value = serde_json::from_str(&text)?;
+        match value {
+            serde_json::Value::Array(values) => values
+                .into_iter()
+                .map(read_snapshot)
+                .collect::<io::Result<Vec<_>>>()?,
+            ref value if value.get("traceEvents").is_some() => read_trace(value)?
+                .into_iter()
+                .map(Snapshot::from_report)
+                .collect(),
+            value => vec![read_snapshot(value)?],
+        }
+    } else {
+        read_table(&text)?
+            .into_iter()
+            .map(Snapshot::from_report)
+            .collect()
+    };
+    for snapshot in &snapshots {
+        check(&snapshot.report)?;
+    }
+    Ok(snapshots)
+}
+
+/// A snapshot, or a report as a snapshot.
+fn read_snapshot(value: serde_json::Value) -> io::Result<Snapshot> {
+    if value.get("version").is_some() {
+        Snapshot::read(value.to_string().as_bytes())
+    } else {
+        let report: Report = serde_json::from_value(value)?;
+        Ok(Snapshot::from_report(report))
+    }
+}
+
+/// Fail unless each entry of `report` has its own index, and is nested in an earlier entry at
+/// the right depth, if at all. Printing the report relies on this.
+fn check(report: &Report) -> io::Result<()> {
+    for (i, e) in report.entries.iter().enumerate() {
+        if e.index != i {
+            return Err(invalid(&format!("entry {} has index {}", i, e.index)));
+        }
+        let depth = match e.parent {
+            Some(p) if p < i => report.entries[p].depth + 1,
+            Some(p) => {
+                return Err(invalid(&format!(
+                    "entry {} has parent {}, which isn't an earlier entry",
+                    i, p
+                )))
+            }
+            None => 0,
+        };
+        if e.depth != depth {
+            return Err(invalid(&format!(
+                "entry {} has depth {}, but its parent says {}",
+                i, e.depth, depth
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The extension of `path`.
+fn extension(path: &str) -> Option<&str> {
+    Path::new(path).extension().and_then(|e| e.to_str())
+}
+
+/// Write `snapshots` to `path`, as `to` or else as the extension of `path` says.
+fn write(snapshots: &[Snapshot], path: &str, to: Option<&str>) -> Result<(), Error> {
+    let reports = || {
+        snapshots
+            .iter()
+            .map(|s| s.report.clone())
+            .collect::<Vec<_>>()
+    };
+    match to.or_else(|| extension(path)) {
+        Some("json") if snapshots.len() == 1 => snapshots[0].save(path)?,
+        Some("json") => {
+            let mut w = BufWriter::new(File::create(path)?);
+            serde_json::to_writer(&mut w, snapshots).map_err(io::Error::from)?;
+            w.flush()?;
+        }
+        Some("csv") => write_table(&reports(), path, Separator::Comma)?,
+        Some("tsv") => write_table(&reports(), path, Separator::Tab)?,
+        Some("chrome") | Some("trace") => {
+            let mut trace = Trace::new();
+            for snapshot in snapshots {
+                trace.add_report(&snapshot.report);
+            }
+            trace.save(path)?;
+        }
+        _ => return Err(Error::Usage),
+    }
+    Ok(())
+}
+
+/// Write the statistics of `snapshot` to `path`, as its extension says. Traces have no place for
+/// statistics.
+fn write_stats(snapshot: &Snapshot, path: &str) -> Result<(), Error> {
+    let separator = match extension(path) {
+        Some("json") => return Ok(snapshot.save(path)?),
+        Some("csv") => Separator::Comma,
+        Some("tsv") => Separator::Tab,
+        _ => return Err(Error::Usage),
+    };
+    let w = BufWriter::new(File::create(path)?);
+    Ok(csv::write_stats(w, &snapshot.stats, separator)?)
+}
+
+fn write_table(reports: &[Report], path: &str, separator: Separator) -> io::Result<()> {
+    csv::write_reports(BufWriter::new(File::create(path)?), reports, separator)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Flat reports of the complete events in a Chrome trace, one for each thread.
+fn read_trace(value: &serde_json::Value) -> io::Result<Vec<Report>> {
+    let events = value["traceEvents"]
+        .as_array()
+        .ok_or_else(|| invalid("`traceEvents` is not a list"))?;
+    let mut sections = Vec::new();
+    for e in events.iter().filter(|e| e["ph"] == "X") {
+        let name = e["name"]
+            .as_str()
+            .ok_or_else(|| invalid("event without a name"))?;
+        let ts = e["ts"]
+            .as_f64()
+            .ok_or_else(|| invalid("event without `ts`"))?;
+        let dur = e["dur"]
+            .as_f64()
+            .ok_or_else(|| invalid("event without `dur`"))?;
+        let thread = e["tid"].as_u64().unwrap_or(0);
+        let mut fields = Fields::new();
+        if let Some(args) = e["args"].as_object() {
+            for (key, value) in args {
+                let value = match *value {
+                    serde_json::Value::Bool(b) => Value::Bool(b),
+                    serde_json::Value::Number(ref n) => match n.as_i64() {
+                        Some(i) => Value::Int(i),
+                        None => Value::Float(n.as_f64().unwrap_or(0.0)),
+                    },
+                    serde_json::Value::String(ref s) => Value::Str(s.clone()),
+                    _ => continue,
+                };
+                fields.insert(key.clone(), value);
+            }
+        }
+        sections.push(Section {
+            label: name.to_string(),
+            start_ns: (ts * 1_000.0) as u64,
+            duration_ns: (dur * 1_000.0) as u64,
+            thread,
+            fields,
+        });
+    }
+    let origin = sections.iter().map(|s| s.start_ns).min().unwrap_or(0);
+    for s in &mut sections {
+        s.start_ns -= origin;
+    }
+    Ok(flat(sections, origin))
+}
+
+/// Flat reports of a file written by `csv::write_report`, with either separator, one for each
+/// thread.
+fn read_table(text: &str) -> io::Result<Vec<Report>> {
+    let mut rows = text.lines();
+    let header = rows.next().unwrap_or("").trim_end();
+    let separator = if header.contains('\t') { '\t' } else { ',' };
+    let header = split_row(header, separator);
+    if header.len() < csv::REPORT_COLUMNS.len() || header[..5] != csv::REPORT_COLUMNS[..] {
+        return Err(invalid("not a report: unknown CSV header"));
+    }
+    let mut sections = Vec::new();
+    for row in rows.filter(|r| !r.trim().is_empty()) {
+        let cells = split_row(row, separator);
+        if cells.len() != header.len() {
+            return Err(invalid("a row doesn't have a cell for each column"));
+        }
+        let number = |i: usize| {
+            cells[i]
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| invalid("a cell is not a number"))
+        };
+        sections.push(Section {
+            label: cells[0].clone(),
+            start_ns: number(2)?,
+            duration_ns: number(3)?,
+            thread: number(4)?,
+            fields: header[5..]
+                .iter()
+                .zip(cells[5..].iter())
+                .filter(|(_, cell)| !cell.is_empty())
+                .map(|(key, cell)| (field_key(key), parse_value(cell)))
+                .collect(),
+        });
+    }
+    Ok(flat(sections, 0))
+}
+
+/// The field key of a column, undoing the `field.` prefix of keys like `label`.
+fn field_key(column: &str) -> String {
+    match column.strip_prefix("field.") {
+        Some(key) if csv::REPORT_COLUMNS.contains(&key) => key.to_string(),
+        _ => column.to_string(),
+    }
+}
+
+/// A field value from a cell, which is a number or a `bool` if it looks like one.
+fn parse_value(cell: &str) -> Value {
+    if let Ok(i) = cell.parse::<i64>() {
+        Value::Int(i)
+    } else if let Ok(x) = cell.parse::<f64>() {
+        Value::Float(x)
+    } else if let Ok(b) = cell.parse::<bool>() {
+        Value::Bool(b)
+    } else {
+        Value::Str(cell.to_string())
+    }
+}
+
+/// Split a row into its cells. With `,` cells can be quoted, but can't span lines.
+fn split_row(row: &str, separator: char) -> Vec<String> {
+    if separator == '\t' {
+        return row.split('\t').map(|c| c.to_string()).collect();
+    }
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = row.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => cells.push(::std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+}
+
+/// A section read from a CSV file or a trace.
+struct Section {
+    label: String,
+    start_ns: u64,
+    duration_ns: u64,
+    thread: u64,
+    fields: Fields,
+}
+
+/// Reports without nesting, one for each thread, in the order the threads first appear.
+fn flat(sections: Vec<Section>, origin_ns: u64) -> Vec<Report> {
+    let mut reports: Vec<Report> = Vec::new();
+    for s in sections {
+        let report = match reports.iter().position(|r| r.thread == s.thread) {
+            Some(i) => &mut reports[i],
+            None => {
+                reports.push(Report {
+                    origin_ns,
+                    thread: s.thread,
+                    total_ns: 0,
+                    entries: Vec::new(),
+                });
+                reports.last_mut().unwrap()
+            }
+        };
+        report.total_ns = report.total_ns.max(s.start_ns + s.duration_ns);
+        let index = report.entries.len();
+        report.entries.push(Entry {
+            label: s.label,
+            index,
+            parent: None,
+            depth: 0,
+            start_ns: s.start_ns,
+            duration_ns: s.duration_ns,
+            cpu: None,
+            fields: s.fields,
+            throughput: None,
+            budget_ns: None,
+        });
+    }
+    reports
+}
@@ -0,0 +1,133 @@
+//! Time budgets for labels, like "a frame update must take less than 16ms".
+//!
+//! Budgets set with `budget::set` apply to `timed!`, `scope` and all `Timer`s. A `Timer` can also
+//! have its own with `Timer::set_budget`, which take precedence. Each section over its budget is
+//! flagged by `present`, with the time it went over, and counted in `violations`. A callback set
+//! with `on_violation` is called for each, as the section ends.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # use std::time::Duration;
+//! # use tid::{budget, MockClock, Timer};
+//! # use tid::sink::Memory;
+//! # fn main() {
+//! budget::set("update", Duration::from_millis(16));
+//! budget::on_violation(|v| {
+//!     eprintln!("{} went {}ns over budget", v.label, v.overshoot_ns());
+//! });
+//!
+//! let clock = MockClock::new();
+//! let mut t = Timer::with_clock(clock.clone());
+//! clock.advance(Duration::from_millis(12));
+//! t.mark("update");
+//! clock.advance(Duration::from_millis(19));
+//! t.mark("update");
+//! # #[cfg(feature = "enabled")]
+//! assert_eq!(t.violations(), 1);
+//!
+//! let memory = Memory::new();
+//! t.set_sink(memory.clone());
+//! t.set_format(tid::format::Format::new().listing(tid::format::Listing::Raw));
+//! t.present();
+//! # #[cfg(feature = "enabled")]
+//! assert_eq!(
+//!     memory.lines(),
+//!     vec![
+//!         "\t[timer] update                       12.0000ms",
+//!         "\t[timer] update                       19.0000ms !!    3.0000ms over budget",
+//!     ]
+//! );
+//! # }
+//! ```
+//!
+//! To make budgets fail loudly in debug builds, panic in the callback:
+//!
+//! ```no_run
+//! tid::budget::on_violation(|v| {
+//!     debug_assert!(false, "{} is over budget: {:?}", v.label, v);
+//! });
+//! ```
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+/// A section which took longer than its budget.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub label: String,
+    pub budget_ns: u64,
+    pub duration_ns: u64,
+}
+
+impl Violation {
+    /// How much longer than the budget the section took.
+    pub fn overshoot_ns(&self) -> u64 {
+        self.duration_ns - self.budget_ns
+    }
+}
+
+type Callback = Arc<dyn Fn(&Violation) + Send + Sync>;
+
+/// Whether `BUDGETS` is non-empty, so that labels without budgets don't need the lock.
+static ANY: AtomicBool = AtomicBool::new(false);
+static BUDGETS: RwLock<Vec<(String, u64)>> = RwLock::new(Vec::new());
+static VIOLATIONS: AtomicU64 = AtomicU64::new(0);
+static CALLBACK: RwLock<Option<Callback>> = RwLock::new(None);
+
+/// Set the budget of `label`, for `timed!`, `scope` and all `Timer`s.
+pub fn set<L: Into<String>>(label: L, budget: Duration) {
+    let label = label.into();
+    let mut budgets = BUDGETS.write().unwrap_or_else(|e| e.into_inner());
+    budgets.retain(|(l, _)| *l != label);
+    budgets.push((label, ::nanos(budget)));
+    ANY.store(true, Ordering::Relaxed);
+}
+
+/// Remove all budgets set with `set`.
+pub fn clear() {
+    BUDGETS.write().unwrap_or_else(|e| e.into_inner()).clear();
+    ANY.store(false, Ordering::Relaxed);
+}
+
+/// Call `f` for every section which goes over its budget, replacing any earlier callback.
+pub fn on_violation<F: Fn(&Violation) + Send + Sync + 'static>(f: F) {
+    *CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(f));
+}
+
+/// The number of sections over their budget so far, in the whole process.
+pub fn violations() -> u64 {
+    VIOLATIONS.load(Ordering::Relaxed)
+}
+
+/// The budget of `label` set with `set`, if any.
+pub(crate) fn lookup(label: &str) -> Option<u64> {
+    if !ANY.load(Ordering::Relaxed) {
+        return None;
+    }
+    BUDGETS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(l, _)| l == label)
+        .map(|&(_, ns)| ns)
+}
+
+/// Count and report a section of `duration_ns` with the given budget, if it's over. Returns
+/// whether it was.
+pub(crate) fn check(label: &str, duration_ns: u64, budget_ns: u64) -> bool {
+    if duration_ns <= budget_ns {
+        return false;
+    }
+    VIOLATIONS.fetch_add(1, Ordering::Relaxed);
+    let callback = CALLBACK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(callback) = callback {
+        callback(&Violation {
+            label: label.to_string(),
+            budget_ns,
+            duration_ns,
+        });
+    }
+    true
+}
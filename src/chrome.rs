@@ -0,0 +1,185 @@
+//! Export timings in the Trace Event Format, which can be opened in `chrome://tracing` or
+//! [Perfetto](https://ui.perfetto.dev).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # #[macro_use] extern crate tid;
+//! # use tid::Timer;
+//! # use tid::chrome::{self, Trace};
+//! # fn parse() {  }
+//! # fn main() {
+//! chrome::collect_timed(true);
+//! let mut t = Timer::new();
+//! t.enter("load");
+//! timed!("parse", parse());
+//! t.mark("parse");
+//! t.exit();
+//!
+//! let mut trace = Trace::new();
+//! trace.add_report(&t.report());
+//! trace.add_timed();
+//! trace.save("trace.json").unwrap();
+//! # }
+//! ```
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use fields::{Fields, Value};
+use report::Report;
+
+static COLLECT: AtomicBool = AtomicBool::new(false);
+static TIMED: Mutex<Vec<Event>> = Mutex::new(Vec::new());
+
+/// Start or stop collecting `timed!` blocks and `scope`s, so that they can be added to a `Trace`
+/// with `Trace::add_timed`. Collection is off by default.
+pub fn collect_timed(on: bool) {
+    COLLECT.store(on, Ordering::Relaxed);
+}
+
+// Called for every `timed!` block and `Scope`.
+pub(crate) fn timed(label: &str, t0: u64, t1: u64, fields: &Fields) {
+    if COLLECT.load(Ordering::Relaxed) {
+        let event = Event {
+            name: label.to_string(),
+            category: "timed",
+            start_ns: t0,
+            duration_ns: t1 - t0,
+            thread: ::thread_id(),
+            args: fields.clone(),
+        };
+        TIMED.lock().unwrap_or_else(|e| e.into_inner()).push(event);
+    }
+}
+
+/// A complete ("X") event.
+struct Event {
+    name: String,
+    category: &'static str,
+    start_ns: u64,
+    duration_ns: u64,
+    thread: u64,
+    /// The fields of the section, written as the event's `args`.
+    args: Fields,
+}
+
+/// A list of events to write out as a trace.
+#[derive(Default)]
+pub struct Trace {
+    events: Vec<Event>,
+}
+
+impl Trace {
+    /// Create an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add all sections of a `Timer`, on the thread the `Timer` was created on.
+    pub fn add_report(&mut self, report: &Report) {
+        self.events.extend(report.entries.iter().map(|e| Event {
+            name: e.label.clone(),
+            category: "timer",
+            start_ns: report.origin_ns + e.start_ns,
+            duration_ns: e.duration_ns,
+            thread: report.thread,
+            args: e.fields.clone(),
+        }));
+    }
+
+    /// Add, and forget, all `timed!` blocks and `scope`s collected since the last call. See
+    /// `collect_timed`.
+    pub fn add_timed(&mut self) {
+        let mut timed = TIMED.lock().unwrap_or_else(|e| e.into_inner());
+        self.events.append(&mut timed);
+    }
+
+    /// Write the trace as JSON to `w`.
+    ///
+    /// ```
+    /// # use tid::Timer;
+    /// # use tid::chrome::Trace;
+    /// let mut t = Timer::new();
+    /// t.mark("say \"hi\"");
+    /// let mut trace = Trace::new();
+    /// trace.add_report(&t.report());
+    /// let mut out = Vec::new();
+    /// trace.write(&mut out).unwrap();
+    /// let out = String::from_utf8(out).unwrap();
+    /// # #[cfg(feature = "enabled")]
+    /// assert!(out.starts_with("{\"traceEvents\":[{\"name\":\"say \\\"hi\\\"\",\"cat\":\"timer\",\"ph\":\"X\""));
+    /// ```
+    pub fn write<W: Write>(&self, mut w: W) -> io::Result<()> {
+        let pid = process::id();
+        write!(w, "{{\"traceEvents\":[")?;
+        for (i, e) in self.events.iter().enumerate() {
+            if i > 0 {
+                write!(w, ",")?;
+            }
+            write!(
+                w,
+                "{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":{},\"tid\":{}",
+                escape(&e.name),
+                e.category,
+                e.start_ns as f64 / 1_000.0,
+                e.duration_ns as f64 / 1_000.0,
+                pid,
+                e.thread
+            )?;
+            if !e.args.is_empty() {
+                write!(w, ",\"args\":{{")?;
+                for (i, (key, value)) in e.args.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, ",")?;
+                    }
+                    write!(w, "\"{}\":{}", escape(key), json(value))?;
+                }
+                write!(w, "}}")?;
+            }
+            write!(w, "}}")?;
+        }
+        write!(w, "],\"displayTimeUnit\":\"ms\"}}")
+    }
+
+    /// Write the trace to the file at `path`, replacing it if it exists.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut w = BufWriter::new(File::create(path)?);
+        self.write(&mut w)?;
+        w.flush()
+    }
+}
+
+/// `value` as JSON. Floats which JSON can't hold are written as `null`.
+fn json(value: &Value) -> String {
+    match *value {
+        Value::Bool(b) => b.to_string(),
+        Value::Int(i) => i.to_string(),
+        Value::Float(x) if x.is_finite() => x.to_string(),
+        Value::Float(_) => "null".to_string(),
+        Value::Str(ref s) => format!("\"{}\"", escape(s)),
+    }
+}
+
+/// Escape `s` for use in a JSON string.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
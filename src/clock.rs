@@ -0,0 +1,79 @@
+//! Where `Timer`s get their time samples from.
+//!
+//! `Timer::new` uses the `Monotonic` clock. In tests you can use a `MockClock` with
+//! `Timer::with_clock` instead, to get exactly the timings you want:
+//!
+//! ```
+//! # use std::time::Duration;
+//! # use tid::{MockClock, Timer};
+//! # use tid::sink::Memory;
+//! let clock = MockClock::new();
+//! let mut t = Timer::with_clock(clock.clone());
+//! clock.advance(Duration::from_millis(3));
+//! t.mark("parse");
+//! clock.advance(Duration::from_micros(1500));
+//! t.mark("validate");
+//!
+//! let memory = Memory::new();
+//! t.set_sink(memory.clone());
+//! t.present();
+//! # #[cfg(feature = "enabled")]
+//! assert_eq!(
+//!     memory.lines(),
+//!     vec![
+//!         "\t[timer] parse                         3.0000ms",
+//!         "\t[timer] validate                      1.5000ms",
+//!     ]
+//! );
+//! ```
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use time;
+
+/// A source of time samples.
+pub trait Clock: Send + Sync {
+    /// The current time in nanoseconds, from some fixed point. This must never go backwards.
+    fn now(&self) -> u64;
+}
+
+/// The system's monotonic clock. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Monotonic;
+
+impl Clock for Monotonic {
+    fn now(&self) -> u64 {
+        time::precise_time_ns()
+    }
+}
+
+/// A clock which only moves when told to. Clones share the same time, so you can keep a clone to
+/// move the time of a `Timer`.
+#[derive(Debug, Clone, Default)]
+pub struct MockClock {
+    now: Arc<AtomicU64>,
+}
+
+impl MockClock {
+    /// Create a clock starting at 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the clock forward by `d`.
+    pub fn advance(&self, d: Duration) {
+        self.advance_ns(::nanos(d));
+    }
+
+    /// Move the clock forward by `ns` nanoseconds.
+    pub fn advance_ns(&self, ns: u64) {
+        self.now.fetch_add(ns, Ordering::SeqCst);
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
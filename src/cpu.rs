@@ -0,0 +1,100 @@
+//! CPU time, next to wall time.
+//!
+//! Wall time alone can't tell if a slow section was busy computing or waiting on something.
+//! With CPU time measured, `present` prints the CPU time of the thread, how much of the wall time
+//! that is, and the CPU time of the whole process:
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # use tid::Timer;
+//! # fn main() {
+//! let mut t = Timer::new();
+//! t.measure_cpu(true);
+//! std::thread::sleep(std::time::Duration::from_millis(2));
+//! t.mark("sleeping");
+//! t.present();
+//!
+//! tid::cpu::measure_timed(true);
+//! timed!("summing", (0..1000).sum::<u64>());
+//! # }
+//! ```
+//!
+//! ```text
+//! [timer] sleeping                      2.0712ms cpu    0.0139ms   0.7% proc    0.0153ms
+//! ```
+//!
+//! CPU time is only available on unix systems. Elsewhere nothing is measured.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(unix)]
+use libc;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// CPU time of the current thread and of the whole process, in nanoseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CpuTime {
+    pub thread_ns: u64,
+    pub process_ns: u64,
+}
+
+impl CpuTime {
+    /// Read the CPU clocks, or `None` if they're not available.
+    pub fn now() -> Option<CpuTime> {
+        Some(CpuTime {
+            thread_ns: read(Clock::Thread)?,
+            process_ns: read(Clock::Process)?,
+        })
+    }
+
+    /// CPU time spent from `earlier` until `self`.
+    pub fn since(&self, earlier: &CpuTime) -> CpuTime {
+        CpuTime {
+            thread_ns: self.thread_ns.saturating_sub(earlier.thread_ns),
+            process_ns: self.process_ns.saturating_sub(earlier.process_ns),
+        }
+    }
+}
+
+static TIMED: AtomicBool = AtomicBool::new(false);
+
+/// Start or stop measuring CPU time in `timed!` blocks and `scope`s. This is off by default.
+pub fn measure_timed(on: bool) {
+    TIMED.store(on, Ordering::Relaxed);
+}
+
+/// Read the CPU clocks if `measure_timed` is on.
+pub(crate) fn timed() -> Option<CpuTime> {
+    if TIMED.load(Ordering::Relaxed) {
+        CpuTime::now()
+    } else {
+        None
+    }
+}
+
+enum Clock {
+    Thread,
+    Process,
+}
+
+#[cfg(unix)]
+fn read(clock: Clock) -> Option<u64> {
+    let id = match clock {
+        Clock::Thread => libc::CLOCK_THREAD_CPUTIME_ID,
+        Clock::Process => libc::CLOCK_PROCESS_CPUTIME_ID,
+    };
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    if unsafe { libc::clock_gettime(id, &mut ts) } != 0 {
+        return None;
+    }
+    Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64)
+}
+
+#[cfg(not(unix))]
+fn read(_clock: Clock) -> Option<u64> {
+    None
+}
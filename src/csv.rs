@@ -0,0 +1,234 @@
+//! Export timings as CSV or TSV, for spreadsheets.
+//!
+//! Reports are written with one row per section, and the columns
+//! `label,index,start_ns,duration_ns,thread`, followed by one column for each field key used in
+//! the report, in sorted order. `write_reports` writes several reports into one file. Sections
+//! without a field get an empty cell. Field keys which are also one of those first columns, like
+//! `label`, get the column `field.label` instead.
+//!
+//! Statistics, like the ones from `tid::registry::stats`, are written with one row per label. The
+//! `thread` column is empty for them, and filled in by `write_stats_by_thread`.
+//!
+//! In CSV, cells with a `,`, a `"` or a line break are quoted. TSV has no quoting, so tabs and
+//! line breaks in cells are replaced by spaces.
+//!
+//! # Examples
+//!
+//! ```
+//! # use tid::Timer;
+//! # use tid::csv::{self, Separator};
+//! let mut t = Timer::new();
+//! t.mark_with("parse", &[("file", "a.csv".into())]);
+//! t.mark("validate, all");
+//!
+//! let mut out = Vec::new();
+//! csv::write_report(&mut out, &t.report(), Separator::Comma).unwrap();
+//! let out = String::from_utf8(out).unwrap();
+//! let lines = out.lines().collect::<Vec<_>>();
+//! # #[cfg(feature = "enabled")] {
+//! assert_eq!(lines[0], "label,index,start_ns,duration_ns,thread,file");
+//! assert!(lines[1].starts_with("parse,0,0,"));
+//! assert!(lines[1].ends_with(",a.csv"));
+//! assert!(lines[2].starts_with("\"validate, all\",1,"));
+//! # }
+//!
+//! let mut t = Timer::new();
+//! t.mark_with("read", &[("thread", "io".into())]);
+//! let mut out = Vec::new();
+//! csv::write_report(&mut out, &t.report(), Separator::Comma).unwrap();
+//! let out = String::from_utf8(out).unwrap();
+//! # #[cfg(feature = "enabled")]
+//! assert!(out.starts_with("label,index,start_ns,duration_ns,thread,field.thread\n"));
+//!
+//! let mut out = Vec::new();
+//! csv::write_stats(&mut out, &tid::registry::stats(), Separator::Tab).unwrap();
+//! ```
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+use std::slice;
+
+use report::Report;
+use stats::Stats;
+
+/// What separates the cells of a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Separator {
+    /// CSV.
+    Comma,
+    /// TSV.
+    Tab,
+}
+
+impl Separator {
+    fn as_str(self) -> &'static str {
+        match self {
+            Separator::Comma => ",",
+            Separator::Tab => "\t",
+        }
+    }
+
+    /// Quote or clean up `cell` so that it's a single cell.
+    fn cell(self, cell: &str) -> String {
+        match self {
+            Separator::Comma if cell.contains([',', '"', '\n', '\r']) => {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            }
+            Separator::Tab => cell.replace(['\t', '\n', '\r'], " "),
+            _ => cell.to_string(),
+        }
+    }
+
+    fn row<W: Write>(self, w: &mut W, cells: &[String]) -> io::Result<()> {
+        let cells = cells.iter().map(|c| self.cell(c)).collect::<Vec<_>>();
+        writeln!(w, "{}", cells.join(self.as_str()))
+    }
+}
+
+/// The columns written by `write_report` before the field columns.
+pub const REPORT_COLUMNS: [&str; 5] = ["label", "index", "start_ns", "duration_ns", "thread"];
+
+/// The columns written by `write_stats`.
+pub const STATS_COLUMNS: [&str; 11] = [
+    "label",
+    "thread",
+    "count",
+    "total_ns",
+    "min_ns",
+    "max_ns",
+    "mean_ns",
+    "median_ns",
+    "stddev_ns",
+    "p90_ns",
+    "p99_ns",
+];
+
+/// Write one row for each section of `report`, after a header. See the module documentation.
+pub fn write_report<W: Write>(w: W, report: &Report, separator: Separator) -> io::Result<()> {
+    write_reports(w, slice::from_ref(report), separator)
+}
+
+/// Write the sections of all `reports`, like the reports of several threads, after a single
+/// header. The field columns are the keys used in any of the reports.
+///
+/// ```
+/// # use tid::Timer;
+/// # use tid::csv::{self, Separator};
+/// let mut a = Timer::new();
+/// a.mark("main");
+/// let b = std::thread::spawn(|| {
+///     let mut b = Timer::new();
+///     b.mark_with("worker", &[("rows", 12.into())]);
+///     b.report()
+/// });
+/// let reports = vec![a.report(), b.join().unwrap()];
+/// let mut out = Vec::new();
+/// csv::write_reports(&mut out, &reports, Separator::Comma).unwrap();
+/// let out = String::from_utf8(out).unwrap();
+/// # #[cfg(feature = "enabled")]
+/// assert_eq!(out.lines().count(), 3);
+/// ```
+pub fn write_reports<W: Write>(
+    mut w: W,
+    reports: &[Report],
+    separator: Separator,
+) -> io::Result<()> {
+    let keys = reports
+        .iter()
+        .flat_map(|r| r.entries.iter())
+        .flat_map(|e| e.fields.keys())
+        .collect::<BTreeSet<_>>();
+    let header = REPORT_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .chain(keys.iter().map(|k| field_column(k)))
+        .collect::<Vec<_>>();
+    separator.row(&mut w, &header)?;
+    for report in reports {
+        for e in &report.entries {
+            let mut row = vec![
+                e.label.clone(),
+                e.index.to_string(),
+                e.start_ns.to_string(),
+                e.duration_ns.to_string(),
+                report.thread.to_string(),
+            ];
+            row.extend(
+                keys.iter()
+                    .map(|&k| e.fields.get(k).map(|v| v.to_string()).unwrap_or_default()),
+            );
+            separator.row(&mut w, &row)?;
+        }
+    }
+    w.flush()
+}
+
+/// The column of the field `key`. See the module documentation.
+fn field_column(key: &str) -> String {
+    if REPORT_COLUMNS.contains(&key) {
+        format!("field.{}", key)
+    } else {
+        key.to_string()
+    }
+}
+
+/// Write one row for each label in `stats`, after a header. The `thread` column is empty.
+pub fn write_stats<W: Write>(w: W, stats: &[Stats], separator: Separator) -> io::Result<()> {
+    write_rows(w, &[(None, stats)], separator)
+}
+
+/// Write one row for each label of each thread, like `registry::stats_by_thread` gives them,
+/// after a header.
+///
+/// ```
+/// # use tid::Stats;
+/// # use tid::csv::{self, Separator};
+/// let stats = vec![(1, vec![Stats::from_samples("parse", &[10, 20])])];
+/// let mut out = Vec::new();
+/// csv::write_stats_by_thread(&mut out, &stats, Separator::Comma).unwrap();
+/// let out = String::from_utf8(out).unwrap();
+/// assert!(out.lines().nth(1).unwrap().starts_with("parse,1,2,30,"));
+/// ```
+pub fn write_stats_by_thread<W: Write>(
+    w: W,
+    stats: &[(u64, Vec<Stats>)],
+    separator: Separator,
+) -> io::Result<()> {
+    let threads = stats
+        .iter()
+        .map(|(thread, stats)| (Some(*thread), &stats[..]))
+        .collect::<Vec<_>>();
+    write_rows(w, &threads, separator)
+}
+
+fn write_rows<W: Write>(
+    mut w: W,
+    threads: &[(Option<u64>, &[Stats])],
+    separator: Separator,
+) -> io::Result<()> {
+    let header = STATS_COLUMNS
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>();
+    separator.row(&mut w, &header)?;
+    for &(thread, stats) in threads {
+        for s in stats {
+            separator.row(
+                &mut w,
+                &[
+                    s.label.clone(),
+                    thread.map(|t| t.to_string()).unwrap_or_default(),
+                    s.count.to_string(),
+                    s.total_ns.to_string(),
+                    s.min_ns.to_string(),
+                    s.max_ns.to_string(),
+                    s.mean_ns.to_string(),
+                    s.median_ns.to_string(),
+                    s.stddev_ns.to_string(),
+                    s.p90_ns.to_string(),
+                    s.p99_ns.to_string(),
+                ],
+            )?;
+        }
+    }
+    w.flush()
+}
@@ -0,0 +1,67 @@
+#[cfg(unix)]
+use std::panic;
+#[cfg(unix)]
+use std::sync::Once;
+
+#[cfg(unix)]
+use libc;
+use registry;
+
+/// Print `tid::report` when the process exits normally, that is when `main` returns, also with an
+/// `Err`, or when `std::process::exit` is called. Calling this more than once has no effect.
+///
+/// ```
+/// fn main() -> Result<(), String> {
+///     tid::install_exit_report();
+///     let _s = tid::scope("main");
+///     Err("the report is still printed".to_string())
+/// }
+/// # let _ = main();
+/// ```
+///
+/// This only works on unix systems, and does nothing elsewhere. `ExitReport` works everywhere.
+pub fn install_exit_report() {
+    #[cfg(unix)]
+    {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| unsafe {
+            libc::atexit(at_exit);
+        });
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn at_exit() {
+    // Unwinding out of here would abort the process, so swallow any panic, like from printing to
+    // a closed `stdout`.
+    let _ = panic::catch_unwind(registry::report);
+}
+
+/// Guard which prints `tid::report` when dropped. Put it at the top of `main`, and the report is
+/// printed on every way out of `main`, also when returning an `Err`.
+///
+/// ```
+/// fn main() -> Result<(), String> {
+///     let _report = tid::ExitReport::new();
+///     let _s = tid::scope("main");
+///     Err("the report is still printed".to_string())
+/// }
+/// # let _ = main();
+/// ```
+#[must_use = "the report is printed when the guard is dropped"]
+#[derive(Debug, Default)]
+pub struct ExitReport {
+    _private: (),
+}
+
+impl ExitReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Drop for ExitReport {
+    fn drop(&mut self) {
+        registry::report();
+    }
+}
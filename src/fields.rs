@@ -0,0 +1,106 @@
+//! Key/value fields attached to sections, for context like the number of rows or the file name.
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The fields of a section, sorted by key.
+pub type Fields = BTreeMap<String, Value>;
+
+/// The value of a field. Anything which is a number, a `bool` or a string converts into one.
+///
+/// ```
+/// # use tid::Value;
+/// assert_eq!(Value::from(1200usize), Value::Int(1200));
+/// assert_eq!(Value::from("a.csv").to_string(), "a.csv");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Bool(b) => b.fmt(f),
+            Value::Int(i) => i.fmt(f),
+            Value::Float(x) => x.fmt(f),
+            Value::Str(ref s) => s.fmt(f),
+        }
+    }
+}
+
+macro_rules! from_int {
+    ($($t:ty),*) => ($(
+        impl From<$t> for Value {
+            fn from(v: $t) -> Self {
+                Value::Int(v as i64)
+            }
+        }
+    )*);
+}
+
+// `u64` and `usize` values above `i64::MAX` are clamped.
+macro_rules! from_uint {
+    ($($t:ty),*) => ($(
+        impl From<$t> for Value {
+            fn from(v: $t) -> Self {
+                Value::Int(i64::try_from(v).unwrap_or(i64::MAX))
+            }
+        }
+    )*);
+}
+
+from_int!(i8, i16, i32, i64, isize, u8, u16, u32);
+from_uint!(u64, usize);
+
+impl From<f32> for Value {
+    fn from(v: f32) -> Self {
+        Value::Float(v as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Bool(v)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(v: &'a str) -> Self {
+        Value::Str(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Str(v)
+    }
+}
+
+impl<'a> From<&'a String> for Value {
+    fn from(v: &'a String) -> Self {
+        Value::Str(v.clone())
+    }
+}
+
+/// Turn a list of fields, as given to `Timer::mark_with` and `timed!`, into `Fields`.
+pub(crate) fn collect(fields: &[(&str, Value)]) -> Fields {
+    fields
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect()
+}
@@ -0,0 +1,142 @@
+//! Turn timing on or off, or pick which labels to time, with the `TID` environment variable.
+//!
+//! - `TID=off` (or `0`, `false`, `none`) turns all timing off. `timed!`, `scope` and `Timer`s
+//!   don't even read the clock.
+//! - `TID=on` (or `1`, `true`, `all`, empty, or not set) times everything. This is the default.
+//! - Anything else is a comma separated list of patterns, like `TID=parse*,io::*`. Only labels
+//!   matching one of the patterns are printed. `*` matches any number of characters.
+//!
+//! The variable is only read once, the first time it's needed, so the check is cheap.
+//!
+//! ```
+//! assert!(tid::filter::is_on());
+//! assert!(tid::filter::enabled("io::read"));
+//! ```
+use std::env;
+use std::sync::OnceLock;
+
+enum Filter {
+    All,
+    Off,
+    Patterns(Vec<String>),
+}
+
+fn filter() -> &'static Filter {
+    static FILTER: OnceLock<Filter> = OnceLock::new();
+    FILTER.get_or_init(|| parse(&env::var("TID").unwrap_or_default()))
+}
+
+fn parse(var: &str) -> Filter {
+    match var.trim().to_lowercase().as_str() {
+        "" | "on" | "1" | "true" | "all" | "*" => Filter::All,
+        "off" | "0" | "false" | "none" => Filter::Off,
+        _ => Filter::Patterns(
+            var.split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect(),
+        ),
+    }
+}
+
+impl Filter {
+    fn is_on(&self) -> bool {
+        !matches!(*self, Filter::Off)
+    }
+
+    fn enabled(&self, label: &str) -> bool {
+        match *self {
+            Filter::All => true,
+            Filter::Off => false,
+            Filter::Patterns(ref patterns) => patterns.iter().any(|p| matches(p, label)),
+        }
+    }
+}
+
+/// Is timing on at all? This is false only with `TID=off`.
+pub fn is_on() -> bool {
+    filter().is_on()
+}
+
+/// Should timings with `label` be printed?
+pub fn enabled(label: &str) -> bool {
+    filter().enabled(label)
+}
+
+/// Does `label` match `pattern`, where `*` in the pattern matches any number of characters?
+fn matches(pattern: &str, label: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    if !label.starts_with(first) {
+        return false;
+    }
+    let mut rest = &label[first.len()..];
+    let parts = parts.collect::<Vec<_>>();
+    let last = match parts.split_last() {
+        Some((last, middle)) => {
+            for part in middle {
+                match rest.find(part) {
+                    Some(i) => rest = &rest[i + part.len()..],
+                    None => return false,
+                }
+            }
+            last
+        }
+        // No `*` at all, so the pattern must match exactly.
+        None => return rest.is_empty(),
+    };
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+
+    fn enabled(var: &str, label: &str) -> bool {
+        parse(var).enabled(label)
+    }
+
+    #[test]
+    fn keywords() {
+        assert!(enabled("", "anything"));
+        assert!(enabled("ON", "anything"));
+        assert!(!enabled("Off", "off"));
+        assert!(!enabled(" none ", "anything"));
+        assert!(!parse("FALSE").is_on());
+        assert!(parse("parse*").is_on());
+    }
+
+    #[test]
+    fn patterns() {
+        assert!(enabled("parse*", "parse"));
+        assert!(enabled("parse*", "parse rows"));
+        assert!(!enabled("parse*", "reparse"));
+        assert!(enabled("io::*", "io::read"));
+        assert!(!enabled("io::*", "io:read"));
+        assert!(enabled("*mid*", "mid"));
+        assert!(enabled("*mid*", "a middle"));
+        assert!(!enabled("*mid*", "mi d"));
+    }
+
+    #[test]
+    fn exact_without_star() {
+        assert!(enabled("load", "load"));
+        assert!(!enabled("load", "load all"));
+        assert!(!enabled("load", "reload"));
+    }
+
+    #[test]
+    fn start_and_end_do_not_overlap() {
+        assert!(enabled("a*a", "aa"));
+        assert!(enabled("a*a", "aba"));
+        assert!(!enabled("a*a", "a"));
+    }
+
+    #[test]
+    fn empty_patterns_are_ignored() {
+        assert!(enabled("a,,b", "b"));
+        assert!(!enabled("a,,b", "c"));
+        assert!(!enabled("a,", ""));
+        assert!(enabled(" a , b ", "b"));
+    }
+}
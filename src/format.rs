@@ -0,0 +1,356 @@
+//! How timings are printed.
+//!
+//! The default `Format` prints the way `tid` always has: labels left aligned in a column of 26
+//! characters, and times in milliseconds with four decimals. Use `tid::set_format` to change this
+//! for the whole process, or `Timer::set_format` for a single `Timer`.
+//!
+//! # Examples
+//!
+//! ```
+//! # use tid::Timer;
+//! # use tid::format::{Format, Unit};
+//! # fn f() {  }
+//! # fn g() {  }
+//! let mut t = Timer::new();
+//! t.set_format(
+//!     Format::new()
+//!         .unit(Unit::Auto)
+//!         .label_width(None)
+//!         .precision(2)
+//!         .percent(true)
+//!         .cumulative(true),
+//! );
+//! f();
+//! t.mark("f");
+//! g();
+//! t.mark("g is done");
+//! t.present();
+//! ```
+//!
+//! which prints something like
+//!
+//! ```text
+//! [timer] f            127.90ns  17.0%    127.90ns
+//! [timer] g is done    624.31ns  83.0%    752.21ns
+//! ```
+use std::sync::RwLock;
+
+use cpu::CpuTime;
+use fields::{Fields, Value};
+use report::Report;
+use stats::Stats;
+
+/// The unit times are printed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// Pick the largest unit which gives at least 1, for each time separately.
+    Auto,
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl Unit {
+    fn scale(self, ns: u64) -> (f64, &'static str) {
+        let unit = match self {
+            Unit::Auto if ns < 1_000 => Unit::Nanoseconds,
+            Unit::Auto if ns < 1_000_000 => Unit::Microseconds,
+            Unit::Auto if ns < 1_000_000_000 => Unit::Milliseconds,
+            Unit::Auto => Unit::Seconds,
+            u => u,
+        };
+        match unit {
+            Unit::Nanoseconds => (ns as f64, "ns"),
+            Unit::Microseconds => (ns as f64 / 1_000.0, "µs"),
+            Unit::Seconds => (ns as f64 / 1_000_000_000.0, "s "),
+            _ => (ns as f64 / 1_000_000.0, "ms"),
+        }
+    }
+}
+
+/// How `Timer::present` lists the sections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Listing {
+    /// `Aggregated` if any label is used for more than one section, and `Raw` otherwise.
+    Auto,
+    /// One line for each section, in the order they were started.
+    Raw,
+    /// One line for each label, with statistics over all sections with that label.
+    Aggregated,
+}
+
+/// Settings for printing timings. See the module documentation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Format {
+    unit: Unit,
+    label_width: Option<usize>,
+    precision: usize,
+    percent: bool,
+    cumulative: bool,
+    listing: Listing,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            unit: Unit::Milliseconds,
+            label_width: Some(26),
+            precision: 4,
+            percent: false,
+            cumulative: false,
+            listing: Listing::Auto,
+        }
+    }
+}
+
+static FORMAT: RwLock<Option<Format>> = RwLock::new(None);
+
+/// Set the format used by `timed!`, `scope` and all `Timer`s which don't have their own format.
+pub fn set_format(format: Format) {
+    *FORMAT.write().unwrap_or_else(|e| e.into_inner()) = Some(format);
+}
+
+/// Get the format set with `set_format`, or the default one.
+pub(crate) fn global() -> Format {
+    FORMAT
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+impl Format {
+    /// The default format.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Print times in `unit`. The default is `Unit::Milliseconds`.
+    pub fn unit(mut self, unit: Unit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    /// Pad labels to `width` characters. `None` makes the column as wide as the longest label.
+    /// The default is `Some(26)`.
+    pub fn label_width(mut self, width: Option<usize>) -> Self {
+        self.label_width = width;
+        self
+    }
+
+    /// Print times with `precision` decimals. The default is 4.
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = precision;
+        self
+    }
+
+    /// Add a column with each section's percentage of the total time of the `Timer`.
+    pub fn percent(mut self, percent: bool) -> Self {
+        self.percent = percent;
+        self
+    }
+
+    /// Add a column with the time from the start of the `Timer` to the end of each section.
+    pub fn cumulative(mut self, cumulative: bool) -> Self {
+        self.cumulative = cumulative;
+        self
+    }
+
+    /// Choose between one line per section, or statistics for each label. The default is
+    /// `Listing::Auto`.
+    ///
+    /// ```
+    /// # use tid::Timer;
+    /// # use tid::format::{Format, Listing};
+    /// let mut t = Timer::new();
+    /// for _ in 0..100 {
+    ///     t.mark("parse");
+    /// }
+    /// t.set_format(Format::new().listing(Listing::Aggregated));
+    /// t.present();
+    /// ```
+    ///
+    /// prints something like
+    ///
+    /// ```text
+    /// [timer] parse                      n=100 total    0.0112ms min    0.0001ms median    0.0001ms mean    0.0001ms sd    0.0000ms p90    0.0001ms p99    0.0004ms max    0.0004ms
+    /// ```
+    pub fn listing(mut self, listing: Listing) -> Self {
+        self.listing = listing;
+        self
+    }
+
+    /// Format a single time.
+    ///
+    /// ```
+    /// # use tid::format::{Format, Unit};
+    /// assert_eq!(Format::new().duration(1_500_000), "   1.5000ms");
+    /// assert_eq!(Format::new().unit(Unit::Auto).precision(1).duration(2_300), "      2.3µs");
+    /// ```
+    pub fn duration(&self, ns: u64) -> String {
+        let (value, unit) = self.unit.scale(ns);
+        format!("{:9.prec$}{}", value, unit, prec = self.precision)
+    }
+
+    /// Format the CPU time columns of a section which took `wall_ns` wall time.
+    fn cpu(&self, wall_ns: u64, cpu: &CpuTime) -> String {
+        format!(
+            " cpu {} {:5.1}% proc {}",
+            self.duration(cpu.thread_ns),
+            percent(cpu.thread_ns, wall_ns),
+            self.duration(cpu.process_ns)
+        )
+    }
+
+    /// Flag a section which took `ns` if it's over `budget`, with how much it went over.
+    fn over_budget(&self, ns: u64, budget: Option<u64>) -> String {
+        match budget {
+            Some(budget) if ns > budget => {
+                format!(" !! {} over budget", self.duration(ns - budget))
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Format the line printed by `timed!` and `scope`.
+    pub(crate) fn timed(
+        &self,
+        label: &str,
+        ns: u64,
+        cpu: Option<&CpuTime>,
+        fields: &Fields,
+        budget: Option<u64>,
+    ) -> String {
+        let width = self.label_width.unwrap_or_else(|| label.chars().count());
+        let mut line = format!("[timed] {:<w$} {}", label, self.duration(ns), w = width);
+        if let Some(cpu) = cpu {
+            line += &self.cpu(ns, cpu);
+        }
+        line += &self.over_budget(ns, budget);
+        line += &self::fields(fields);
+        line
+    }
+
+    /// Format the lines printed by `Timer::present`.
+    pub fn report(&self, report: &Report) -> Vec<String> {
+        match self.listing {
+            Listing::Aggregated => return self.stats(&report.stats()),
+            Listing::Auto if report.has_repeats() => return self.stats(&report.stats()),
+            _ => {}
+        }
+        let nested = report.is_nested();
+        let labels = report
+            .entries
+            .iter()
+            .map(|e| format!("{:indent$}{}", "", e.label, indent = e.depth * 2))
+            .collect::<Vec<_>>();
+        let width = self
+            .label_width
+            .unwrap_or_else(|| labels.iter().map(|l| l.chars().count()).max().unwrap_or(0));
+
+        let rates = report.entries.iter().any(|e| e.throughput.is_some());
+
+        let mut lines = Vec::with_capacity(report.entries.len());
+        for (e, label) in report.entries.iter().zip(labels.iter()) {
+            let mut line = format!(
+                "\t[timer] {:<w$} {}",
+                label,
+                self.duration(e.duration_ns),
+                w = width
+            );
+            if rates {
+                let rate = e.throughput.map(|t| t.rate(e.duration_ns));
+                line += &format!(" {:>13}", rate.unwrap_or_default());
+            }
+            if nested {
+                let parent = e
+                    .parent
+                    .map(|p| report.entries[p].duration_ns)
+                    .unwrap_or(report.total_ns);
+                line += &format!(
+                    " {} {:5.1}%",
+                    self.duration(report.self_ns(e.index)),
+                    percent(e.duration_ns, parent)
+                );
+            }
+            if let Some(ref cpu) = e.cpu {
+                line += &self.cpu(e.duration_ns, cpu);
+            }
+            if self.percent {
+                line += &format!(" {:5.1}%", percent(e.duration_ns, report.total_ns));
+            }
+            if self.cumulative {
+                line += &format!(" {}", self.duration(e.start_ns + e.duration_ns));
+            }
+            line += &self.over_budget(e.duration_ns, e.budget_ns);
+            line += &fields(&e.fields);
+            lines.push(line);
+        }
+        lines
+    }
+
+    /// The width of the label column, for `labels`.
+    pub(crate) fn width_of<'a, I: Iterator<Item = &'a str>>(&self, labels: I) -> usize {
+        self.label_width
+            .unwrap_or_else(|| labels.map(|l| l.chars().count()).max().unwrap_or(0))
+    }
+
+    /// Format one line for each label.
+    pub fn stats(&self, stats: &[Stats]) -> Vec<String> {
+        let width = self.width_of(stats.iter().map(|s| &s.label[..]));
+        let rates = stats.iter().any(|s| s.throughput.is_some());
+        stats
+            .iter()
+            .map(|s| {
+                let rate = if rates {
+                    let rate = s.throughput.map(|t| t.rate(s.total_ns));
+                    format!(" {:>13}", rate.unwrap_or_default())
+                } else {
+                    String::new()
+                };
+                let over = if s.over_budget > 0 {
+                    format!(" !! {} over budget", s.over_budget)
+                } else {
+                    String::new()
+                };
+                format!(
+                    "\t[timer] {:<w$} n={} total {}{} min {} median {} mean {} sd {} p90 {} p99 {} max {}{}",
+                    s.label,
+                    s.count,
+                    self.duration(s.total_ns),
+                    rate,
+                    self.duration(s.min_ns),
+                    self.duration(s.median_ns),
+                    self.duration(s.mean_ns.round() as u64),
+                    self.duration(s.stddev_ns.round() as u64),
+                    self.duration(s.p90_ns),
+                    self.duration(s.p99_ns),
+                    self.duration(s.max_ns),
+                    over,
+                    w = width
+                )
+            })
+            .collect()
+    }
+}
+
+/// Fields as ` key=value` pairs. Strings are quoted.
+fn fields(fields: &Fields) -> String {
+    let mut out = String::new();
+    for (key, value) in fields {
+        match *value {
+            Value::Str(ref s) => out += &format!(" {}={:?}", key, s),
+            ref v => out += &format!(" {}={}", key, v),
+        }
+    }
+    out
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        100.0
+    } else {
+        part as f64 / whole as f64 * 100.0
+    }
+}
@@ -0,0 +1,234 @@
+//! Bounded size histograms, for labels which are timed very many times.
+//!
+//! A `Histogram` keeps counts in buckets whose width grows with the value, in the style of
+//! [HdrHistogram](http://hdrhistogram.org/). Every value is kept with at least two significant
+//! digits (the error is at most 1/128). The buckets are allocated 1 KB at a time, for each power
+//! of two in which a value is recorded, so a histogram never uses more than about 60 KB, no
+//! matter how many values are recorded or how large they are, and a few similar values take
+//! little more than 1 KB.
+//!
+//! # Examples
+//!
+//! ```
+//! # use tid::Timer;
+//! # fn step() {  }
+//! let mut t = Timer::new();
+//! t.use_histograms();
+//! for _ in 0..100_000 {
+//!     step();
+//!     t.mark("step");
+//! }
+//! let stats = t.stats();
+//! # #[cfg(feature = "enabled")]
+//! assert_eq!(stats[0].count, 100_000);
+//! t.present();
+//! ```
+use std::collections::HashMap;
+
+use stats::Stats;
+
+/// Bits of precision in each bucket.
+const BITS: u32 = 8;
+const SUB: usize = 1 << BITS;
+const HALF: usize = SUB / 2;
+const LEN: usize = SUB + (64 - BITS as usize) * HALF;
+/// Buckets are allocated in chunks of this many.
+const CHUNK: usize = HALF;
+
+/// A histogram of nanosecond values. See the module documentation.
+///
+/// ```
+/// # use tid::histogram::Histogram;
+/// let mut a = Histogram::new();
+/// let mut b = Histogram::new();
+/// for v in 1..=1000 {
+///     a.record(v * 1_000);
+///     b.record(v * 1_000 + 1_000_000);
+/// }
+/// a.merge(&b);
+/// assert_eq!(a.count(), 2000);
+/// let median = a.percentile(50.0);
+/// assert!((median as f64 - 1_000_000.0).abs() / 1_000_000.0 < 0.01);
+/// ```
+#[derive(Clone)]
+pub struct Histogram {
+    /// `LEN / CHUNK` chunks of counts, each allocated on its first value, or nothing while the
+    /// histogram is empty.
+    counts: Vec<Option<Box<[u64]>>>,
+    count: u64,
+    total: u64,
+    min: u64,
+    max: u64,
+    /// Sum of the squares of all values, for the standard deviation.
+    squares: f64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: Vec::new(),
+            count: 0,
+            total: 0,
+            min: u64::MAX,
+            max: 0,
+            squares: 0.0,
+        }
+    }
+}
+
+impl Histogram {
+    /// Create an empty histogram.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a single value.
+    pub fn record(&mut self, ns: u64) {
+        self.add(index(ns), 1);
+        self.count += 1;
+        self.total = self.total.saturating_add(ns);
+        self.min = self.min.min(ns);
+        self.max = self.max.max(ns);
+        self.squares += ns as f64 * ns as f64;
+    }
+
+    /// Add all values recorded in `other` to `self`.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (chunk, counts) in other.counts.iter().enumerate() {
+            for (i, &c) in counts.iter().flat_map(|c| c.iter()).enumerate() {
+                if c > 0 {
+                    self.add(chunk * CHUNK + i, c);
+                }
+            }
+        }
+        self.count += other.count;
+        self.total = self.total.saturating_add(other.total);
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.squares += other.squares;
+    }
+
+    /// The number of recorded values.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The nearest-rank percentile `p`, between 0 and 100. Returns 0 if the histogram is empty.
+    pub fn percentile(&self, p: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((p / 100.0 * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (chunk, counts) in self.counts.iter().enumerate() {
+            for (i, &c) in counts.iter().flat_map(|c| c.iter()).enumerate() {
+                seen += c;
+                if seen >= rank {
+                    return highest(chunk * CHUNK + i).max(self.min).min(self.max);
+                }
+            }
+        }
+        self.max
+    }
+
+    /// Add `n` to the bucket at `index`, allocating its chunk if needed.
+    fn add(&mut self, index: usize, n: u64) {
+        if self.counts.is_empty() {
+            self.counts.resize(LEN / CHUNK, None);
+        }
+        let chunk =
+            self.counts[index / CHUNK].get_or_insert_with(|| vec![0; CHUNK].into_boxed_slice());
+        chunk[index % CHUNK] += n;
+    }
+
+    /// Statistics over all recorded values. Min, max, total and mean are exact, the rest are
+    /// within the precision of the histogram.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the histogram is empty.
+    pub fn stats(&self, label: &str) -> Stats {
+        assert!(self.count > 0, "no samples for {:?}", label);
+        let n = self.count as f64;
+        let mean = self.total as f64 / n;
+        let variance = (self.squares / n - mean * mean).max(0.0);
+        Stats {
+            label: label.to_string(),
+            count: self.count,
+            total_ns: self.total,
+            min_ns: self.min,
+            max_ns: self.max,
+            mean_ns: mean,
+            median_ns: self.percentile(50.0),
+            stddev_ns: variance.sqrt(),
+            p90_ns: self.percentile(90.0),
+            p99_ns: self.percentile(99.0),
+            throughput: None,
+            over_budget: 0,
+        }
+    }
+}
+
+/// The bucket of `v`.
+fn index(v: u64) -> usize {
+    if v < SUB as u64 {
+        return v as usize;
+    }
+    let shift = 63 - v.leading_zeros() - (BITS - 1);
+    let sub = (v >> shift) as usize;
+    SUB + (shift as usize - 1) * HALF + (sub - HALF)
+}
+
+/// The largest value in the bucket at `index`.
+fn highest(index: usize) -> u64 {
+    if index < SUB {
+        return index as u64;
+    }
+    let shift = (index - SUB) / HALF + 1;
+    let sub = ((index - SUB) % HALF + HALF) as u64;
+    (sub << shift) + ((1 << shift) - 1)
+}
+
+/// One histogram for each label, in the order the labels first appear.
+#[derive(Clone, Default)]
+pub(crate) struct Histograms {
+    index: HashMap<String, usize>,
+    histograms: Vec<(String, Histogram)>,
+}
+
+impl Histograms {
+    pub(crate) fn get_mut(&mut self, label: &str) -> &mut Histogram {
+        let i = match self.index.get(label) {
+            Some(&i) => i,
+            None => {
+                self.histograms.push((label.to_string(), Histogram::new()));
+                self.index
+                    .insert(label.to_string(), self.histograms.len() - 1);
+                self.histograms.len() - 1
+            }
+        };
+        &mut self.histograms[i].1
+    }
+
+    pub(crate) fn record(&mut self, label: &str, ns: u64) {
+        self.get_mut(label).record(ns);
+    }
+
+    pub(crate) fn merge(&mut self, other: &Histograms) {
+        for (label, h) in &other.histograms {
+            self.get_mut(label).merge(h);
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &(String, Histogram)> {
+        self.histograms.iter()
+    }
+
+    pub(crate) fn stats(&self) -> Vec<Stats> {
+        self.histograms
+            .iter()
+            .filter(|(_, h)| h.count() > 0)
+            .map(|(label, h)| h.stats(label))
+            .collect()
+    }
+}
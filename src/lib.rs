@@ -2,17 +2,40 @@
 //!
 //! The `timed!` macro prints the labels as `{:<26}` (left aligned, 26 char length). The only
 //! reason `26` is chosen is because my longest label happend to be around 26 chars long.
-//! The timings are printed as floating points in microseconds, for much of the same reasons.
-//! The exact print format is hardcoded as:
+//! The timings are printed as floating points in milliseconds, for much of the same reasons.
+//! The default print format is:
 //!
 //! ```
 //! # let t1 = 1; let t0 = 0; let label = "hei";
 //! println!("[timed] {:<26} {:9.4}ms", label, (t1 - t0) as f64 / 1_000_000.0);
 //! ```
 //!
+//! This can be changed with `set_format`, see the `format` module. Output goes to `stdout`, unless
+//! another sink is set, see the `sink` module.
+//!
 //! # Examples
 //!
-//! The crate has a macro `timed!` which is used for timing a block:
+//! The crate has a macro `timed!` which is used for timing an expression or a block. The value of
+//! the expression is passed through:
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # fn parse(s: &str) -> usize { s.len() }
+//! # fn main() {
+//! let n = timed!("parsing", parse("some input"));
+//! let v = timed!("pushing some stuff", {
+//!     let mut v = Vec::new();
+//!     for i in 0..n {
+//!         v.push(i);
+//!     }
+//!     v
+//! });
+//! # assert_eq!(v.len(), 10);
+//! # }
+//! ```
+//!
+//! `timed!` can also take a list of statements, each terminated by `;`. Then the statements are
+//! not put in a block of their own, so variables defined inside are reachable afterwards:
 //!
 //! ```
 //! # #[macro_use] extern crate tid;
@@ -27,6 +50,25 @@
 //! # }
 //! ```
 //!
+//! If the block can return early, for instance with `?`, use `scope` instead. The time is taken
+//! when the returned guard is dropped:
+//!
+//! ```
+//! # use std::num::ParseIntError;
+//! fn parse(s: &str) -> Result<i32, ParseIntError> {
+//!     let _s = tid::scope("parsing");
+//!     let n = s.parse::<i32>()?;
+//!     Ok(n * 2)
+//! }
+//! # parse("12").unwrap();
+//! # parse("nope").unwrap_err();
+//! ```
+//!
+//! Whole functions can be timed with the `#[timed]` attribute in the `attr` module.
+//!
+//! Both `timed!` and `Timer` can attach fields like `rows = 1200` to a timing, see `timed!` and
+//! `Timer::mark_with`.
+//!
 //! If you have multiple consecutive blocks, you can use `Timer` instead.
 //!
 //! ```
@@ -44,85 +86,337 @@
 //! t.present();
 //! ```
 //!
+//! # Turning it off
+//!
+//! All timing is done only with the `enabled` feature, which is on by default. With
+//! `default-features = false`, `timed!` expands to just the code it wraps, and `Timer`, `scope` and
+//! `#[timed]` do nothing, so the calls can be left in release builds for free.
+//!
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "macros")]
+extern crate tid_macros;
 extern crate time;
 
+use std::any;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use cpu::CpuTime;
+
+#[cfg(feature = "macros")]
+pub mod attr;
+#[cfg(feature = "serde")]
+pub mod baseline;
+pub mod bench;
+pub mod budget;
+pub mod chrome;
+pub mod clock;
+pub mod cpu;
+pub mod csv;
+mod exit;
+mod fields;
+pub mod filter;
+pub mod format;
+pub mod histogram;
+pub mod perf;
+pub mod registry;
+mod report;
+mod scope;
+pub mod sink;
+mod stats;
+mod throughput;
+mod timer;
+
+pub use clock::{Clock, MockClock};
+pub use exit::install_exit_report;
+pub use exit::ExitReport;
+pub use fields::{Fields, Value};
+pub use format::{set_format, Format};
+pub use registry::report;
+pub use report::{Entry, Report};
+pub use scope::{scope, Scope};
+pub use sink::{set_sink, Sink};
+pub use stats::Stats;
+pub use throughput::Throughput;
+pub use timer::{Section, Timer};
+
+#[cfg(feature = "enabled")]
 #[macro_export]
-/// Time a block of code. For macro reasons, all blocks must be terminated by `;`.
+/// Time an expression, and evaluate to its value. If given a list of statements, each terminated
+/// by `;`, the statements are timed and expanded in place instead.
+///
+/// Fields can be given after the label, separated by `;`. They're evaluated after the timed code,
+/// and only if timing is on, so they can use variables defined by the statements:
+///
+/// ```
+/// # #[macro_use] extern crate tid;
+/// # fn load(file: &str) -> Vec<u32> { vec![1, 2, 3] }
+/// # fn main() {
+/// let file = "a.csv";
+/// timed!("load"; rows = rows.len(), file = file;
+///     let rows = load(file);
+/// );
+/// let sum = timed!("sum"; rows = rows.len(); rows.iter().sum::<u32>());
+/// # assert_eq!(sum, 6);
+/// # }
+/// ```
+///
+/// prints something like
+///
+/// ```text
+/// [timed] load                          0.0101ms file="a.csv" rows=3
+/// [timed] sum                           0.0002ms rows=3
+/// ```
+///
+/// The label can be a `String` as well, and is only borrowed:
+///
+/// ```
+/// # #[macro_use] extern crate tid;
+/// # fn main() {
+/// let name = format!("chunk {}", 3);
+/// timed!(name, let a = 1;);
+/// let n = timed!(name, a + 1);
+/// # assert_eq!((n, name.as_str()), (2, "chunk 3"));
+/// # }
+/// ```
 macro_rules! timed {
+    ($name:expr; $($key:ident = $value:expr),+; $($block:stmt);+;) => (
+        let start = $crate::_start();
+        $($block)+
+        $crate::_timed_with(&$name, start, || vec![$((stringify!($key), $crate::Value::from($value))),+]);
+    );
+    ($name:expr; $($key:ident = $value:expr),+; $e:expr) => ({
+        let start = $crate::_start();
+        let value = $e;
+        $crate::_timed_with(&$name, start, || vec![$((stringify!($key), $crate::Value::from($value))),+]);
+        value
+    });
     ($name:expr, $($block:stmt);+;) => (
-        let t0 = $crate::_time();
-        $($block);+;
-        let t1 = $crate::_time();
-        println!("[timed] {:<26} {:9.4}ms", $name, (t1 - t0) as f64 / 1_000_000.0);
-    )
+        let start = $crate::_start();
+        $($block)+
+        $crate::_timed(&$name, start);
+    );
+    ($name:expr, $e:expr) => ({
+        let start = $crate::_start();
+        let value = $e;
+        $crate::_timed(&$name, start);
+        value
+    });
 }
 
-#[doc(hidden)]
-// Wrap `time::precise_time_ns` so the crate using `tid` doesn't have to depend on `time`.
-// Maybe there is a better way to do this?
-pub fn _time() -> u64 {
-    time::precise_time_ns()
+#[cfg(not(feature = "enabled"))]
+#[macro_export]
+/// Time an expression, and evaluate to its value. If given a list of statements, each terminated
+/// by `;`, the statements are timed and expanded in place instead.
+///
+/// The `enabled` feature is off, so this expands to just the expression or statements. The label
+/// and fields are still referenced, but never evaluated, so that variables used only there don't
+/// warn as unused.
+macro_rules! timed {
+    ($name:expr; $($key:ident = $value:expr),+; $($block:stmt);+;) => (
+        $($block)+
+        if false {
+            let _ = (&$name, $(&$value),+);
+        }
+    );
+    ($name:expr; $($key:ident = $value:expr),+; $e:expr) => ({
+        let value = $e;
+        if false {
+            let _ = (&$name, $(&$value),+);
+        }
+        value
+    });
+    ($name:expr, $($block:stmt);+;) => (
+        $($block)+
+        if false {
+            let _ = &$name;
+        }
+    );
+    ($name:expr, $e:expr) => ({
+        if false {
+            let _ = &$name;
+        }
+        $e
+    });
 }
 
-/// A `Timer` is used for timing multiple consecutive sections of your code. The first timing is
-/// done when the object is constructed. The second timing is done at the first call to `mark`.
-/// This time difference will be the one reported with the label you pass to `mark`.
-///
-/// # Examples
+/// Mark off a section of a `Timer`, with a label made with `format!`. The label is only formatted
+/// if timing is on, so nothing is allocated otherwise.
 ///
 /// ```
+/// # #[macro_use] extern crate tid;
 /// # use tid::Timer;
-/// # fn f() {  }
-/// # fn g() {  }
-/// # fn h() {  }
+/// # fn process(chunk: &[u8]) {  }
+/// # fn main() {
+/// # let chunks = vec![vec![1u8; 4]; 3];
 /// let mut t = Timer::new();
-/// f();
-/// t.mark("Doing f");
-/// g();
-/// t.mark("G is executed");
-/// h();
-/// t.mark("Done with H");
+/// for (i, chunk) in chunks.iter().enumerate() {
+///     process(chunk);
+///     mark!(t, "chunk {}", i);
+/// }
 /// t.present();
+/// # }
 /// ```
 ///
-/// When `present` is called, we print all timings:
+/// Fields can be given after the label, separated by `;`, like with `timed!`. They're passed on to
+/// `Timer::mark_with`:
 ///
-/// ```text
-/// [timer] Doing f          0.12004ms
-/// [timer] G is executed   21.98122ms
-/// [timer] Done with H      7.00124ms
 /// ```
-pub struct Timer {
-    times: Vec<u64>,
-    strs: Vec<&'static str>,
+/// # #[macro_use] extern crate tid;
+/// # use tid::Timer;
+/// # fn main() {
+/// # let rows = vec![1, 2, 3];
+/// let mut t = Timer::new();
+/// mark!(t, "parse"; rows = rows.len(), file = "a.csv");
+/// mark!(t, "chunk {}", 2; rows = rows.len());
+/// # #[cfg(feature = "enabled")]
+/// assert_eq!(t.report().entries[1].fields["rows"], tid::Value::Int(3));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! mark {
+    ($timer:expr, $label:expr; $($key:ident = $value:expr),+) => (
+        if $crate::_enabled() {
+            $timer.mark_with($label, &[$((stringify!($key), $crate::Value::from($value))),+]);
+        }
+    );
+    ($timer:expr, $fmt:expr, $($arg:expr),+; $($key:ident = $value:expr),+) => (
+        if $crate::_enabled() {
+            $timer.mark_with(
+                format!($fmt, $($arg),+),
+                &[$((stringify!($key), $crate::Value::from($value))),+],
+            );
+        }
+    );
+    ($timer:expr, $($arg:tt)+) => (
+        if $crate::_enabled() {
+            $timer.mark(format!($($arg)+));
+        }
+    );
 }
 
-impl Timer {
-    /// Create a new `Timer`. The first time sample is taken here.
-    pub fn new() -> Self {
-        let mut s = Self {
-            times: Vec::with_capacity(100),
-            strs: Vec::with_capacity(100),
-        };
-        s.times.push(time::precise_time_ns());
-        s
+/// Run a block many times, and panic if it's too slow. Keys like `runs = 100` set the options of
+/// `perf::FasterThan`. See the `perf` module.
+///
+/// ```
+/// # #[macro_use] extern crate tid;
+/// # use std::time::Duration;
+/// # fn main() {
+/// let v = (0..1000).collect::<Vec<u32>>();
+/// assert_faster_than!(Duration::from_millis(5), runs = 100, percentile = 99.0, {
+///     v.iter().sum::<u32>()
+/// });
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_faster_than {
+    ($limit:expr, $($key:ident = $value:expr,)* $body:block) => (
+        $crate::perf::FasterThan::new($limit)$(.$key($value))*.run(|| $body)
+    );
+}
+
+/// Whether the `enabled` feature is on. When it's off, nothing is timed.
+pub(crate) const ENABLED: bool = cfg!(feature = "enabled");
+
+/// Whether anything should be timed, from the `enabled` feature and the `TID` variable.
+pub(crate) fn enabled() -> bool {
+    ENABLED && filter::is_on()
+}
+
+#[doc(hidden)]
+pub fn _enabled() -> bool {
+    enabled()
+}
+
+#[doc(hidden)]
+// Wrap `time::precise_time_ns` so the crate using `tid` doesn't have to depend on `time`.
+// Maybe there is a better way to do this?
+pub fn _time() -> u64 {
+    time::precise_time_ns()
+}
+
+#[doc(hidden)]
+// The samples taken at the start of a `timed!` block or a `Scope`.
+// `None` if timing is off.
+pub struct _Start(Option<(u64, Option<CpuTime>)>);
+
+#[doc(hidden)]
+pub fn _start() -> _Start {
+    if !enabled() {
+        return _Start(None);
     }
+    let cpu = cpu::timed();
+    _Start(Some((_time(), cpu)))
+}
+
+#[doc(hidden)]
+// Take the end samples of a `timed!` block or a `Scope`, and report the timing. The label is
+// anything like a string, as `timed!` has always taken `String`s too.
+pub fn _timed<L: AsRef<str> + ?Sized>(label: &L, start: _Start) {
+    _timed_with(label, start, Vec::new)
+}
 
-    /// Mark off a secion with the given label.
-    pub fn mark(&mut self, label: &'static str) {
-        self.times.push(time::precise_time_ns());
-        self.strs.push(label);
+#[doc(hidden)]
+// Like `_timed`, with the fields of a `timed!` block. `fields` is only called if the timing is
+// reported.
+pub fn _timed_with<L, F>(label: &L, start: _Start, fields: F)
+where
+    L: AsRef<str> + ?Sized,
+    F: FnOnce() -> Vec<(&'static str, Value)>,
+{
+    let label = label.as_ref();
+    let (t0, c0) = match start.0 {
+        Some(start) => start,
+        None => return,
+    };
+    let t1 = _time();
+    if !filter::enabled(label) {
+        return;
     }
+    let cpu = match (c0, cpu::timed()) {
+        (Some(c0), Some(c1)) => Some(c1.since(&c0)),
+        _ => None,
+    };
+    let fields: Fields = fields()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    let budget = budget::lookup(label);
+    if let Some(budget) = budget {
+        budget::check(label, t1 - t0, budget);
+    }
+    sink::global().line(&format::global().timed(label, t1 - t0, cpu.as_ref(), &fields, budget));
+    chrome::timed(label, t0, t1, &fields);
+    registry::record(label, t1 - t0);
+}
 
-    /// Print out the timings to `stdout`.
-    pub fn present(self) {
-        let diffs = self.times.iter().zip(self.times.iter().skip(1)).map(
-            |(a, b)| {
-                b - a
-            },
-        );
-        for (time, s) in diffs.zip(self.strs.iter()) {
-            println!("\t[timer] {:<26} {:9.4}ms", s, time as f64 / 1_000_000.0);
-        }
+/// `d` in nanoseconds, or `u64::MAX` if it's longer than that, which is some 584 years.
+pub(crate) fn nanos(d: Duration) -> u64 {
+    u64::try_from(d.as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[doc(hidden)]
+// The path of the function which `f` is defined in, for `#[timed]`. `f` must be a function item
+// named `__tid_f`, whose type name is the path of the function around it, followed by its name.
+pub fn _function_name<F>(_f: F) -> &'static str {
+    let name = any::type_name::<F>();
+    let name = name.strip_suffix("::__tid_f").unwrap_or(name);
+    // In an `async fn` the body is in a closure.
+    name.trim_end_matches("::{{closure}}")
+}
+
+/// A small number identifying the current thread. Threads are numbered from 1, in the order they
+/// first ask for it.
+pub(crate) fn thread_id() -> u64 {
+    static NEXT: AtomicUsize = AtomicUsize::new(1);
+    thread_local! {
+        static ID: u64 = NEXT.fetch_add(1, Ordering::Relaxed) as u64;
     }
+    ID.with(|id| *id)
 }
@@ -0,0 +1,160 @@
+//! Assert that code is fast enough, in plain `#[test]`s.
+//!
+//! `assert_faster_than!` runs a block a number of times, after some warm-up runs which aren't
+//! measured, and panics if a percentile of the times is over the limit. The median is used by
+//! default. A higher percentile is stricter, while the median is more tolerant of noise.
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # use std::time::Duration;
+//! # fn main() {
+//! assert_faster_than!(Duration::from_millis(5), runs = 100, {
+//!     (0..1000u64).sum::<u64>()
+//! });
+//! assert_faster_than!(Duration::from_millis(5), runs = 50, warmup = 5, percentile = 90.0, {
+//!     (0..1000u64).map(|i| i * i).sum::<u64>()
+//! });
+//! # }
+//! ```
+//!
+//! On failure, the panic message has the median and the chosen percentile:
+//!
+//! ```text
+//! too slow: p90 of 50 runs is    6.1042ms, over the limit of    5.0000ms (median    4.9120ms)
+//! ```
+//!
+//! The times are taken even if timing is turned off, with the `enabled` feature or `TID=off`.
+use std::error::Error;
+use std::fmt;
+use std::hint::black_box;
+use std::time::Duration;
+
+use clock::{Clock, Monotonic};
+use format;
+use stats::{self, Stats};
+
+/// The settings of `assert_faster_than!`. The keys given to the macro are the methods here.
+///
+/// ```should_panic
+/// # use std::time::Duration;
+/// # use tid::MockClock;
+/// # use tid::perf::FasterThan;
+/// let clock = MockClock::new();
+/// FasterThan::new(Duration::from_millis(5))
+///     .clock(clock.clone())
+///     .run(|| clock.advance(Duration::from_millis(6)));
+/// ```
+pub struct FasterThan {
+    limit_ns: u64,
+    runs: usize,
+    warmup: usize,
+    percentile: f64,
+    clock: Box<dyn Clock>,
+}
+
+impl FasterThan {
+    /// Require runs to take at most `limit`. By default there are 100 runs after 10 warm-up runs,
+    /// and the median is compared with the limit.
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit_ns: ::nanos(limit),
+            runs: 100,
+            warmup: 10,
+            percentile: 50.0,
+            clock: Box::new(Monotonic),
+        }
+    }
+
+    /// Measure `runs` runs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `runs` is 0.
+    pub fn runs(mut self, runs: usize) -> Self {
+        assert!(runs > 0, "`runs` must be at least 1");
+        self.runs = runs;
+        self
+    }
+
+    /// Run `warmup` times before measuring.
+    pub fn warmup(mut self, warmup: usize) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Compare the percentile `p`, between 0 and 100, with the limit.
+    pub fn percentile(mut self, p: f64) -> Self {
+        self.percentile = p;
+        self
+    }
+
+    /// Take the times from `clock`. See the `clock` module.
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Measure `f`, and return the statistics of the runs, or a `TooSlow` error if the percentile
+    /// is over the limit.
+    pub fn check<F: FnMut() -> R, R>(&self, mut f: F) -> Result<Stats, TooSlow> {
+        for _ in 0..self.warmup {
+            black_box(f());
+        }
+        let mut samples = Vec::with_capacity(self.runs);
+        for _ in 0..self.runs {
+            let t0 = self.clock.now();
+            black_box(f());
+            samples.push(self.clock.now() - t0);
+        }
+        let stats = Stats::from_samples("run", &samples);
+        samples.sort_unstable();
+        let value_ns = stats::percentile(&samples, self.percentile);
+        if value_ns > self.limit_ns {
+            return Err(TooSlow {
+                runs: stats.count,
+                median_ns: stats.median_ns,
+                percentile: self.percentile,
+                value_ns,
+                limit_ns: self.limit_ns,
+            });
+        }
+        Ok(stats)
+    }
+
+    /// Like `check`, but panic if the percentile is over the limit.
+    #[track_caller]
+    pub fn run<F: FnMut() -> R, R>(&self, f: F) -> Stats {
+        match self.check(f) {
+            Ok(stats) => stats,
+            Err(e) => panic!("{}", e),
+        }
+    }
+}
+
+/// The error from `FasterThan::check`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TooSlow {
+    pub runs: u64,
+    pub median_ns: u64,
+    pub percentile: f64,
+    /// The percentile of the run times.
+    pub value_ns: u64,
+    pub limit_ns: u64,
+}
+
+impl fmt::Display for TooSlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let format = format::global();
+        write!(
+            f,
+            "too slow: p{} of {} runs is {}, over the limit of {} (median {})",
+            self.percentile,
+            self.runs,
+            format.duration(self.value_ns),
+            format.duration(self.limit_ns),
+            format.duration(self.median_ns)
+        )
+    }
+}
+
+impl Error for TooSlow {}
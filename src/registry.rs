@@ -0,0 +1,134 @@
+//! A process wide registry of timings, which all threads record into.
+//!
+//! Every `timed!` block and `scope` is recorded here, and `Timer`s can be added with
+//! `Timer::submit`. Each thread records into its own histograms, so threads don't wait on each
+//! other. When a thread exits its histograms are added to those of all earlier exited threads, so
+//! short lived threads don't add up. `tid::report` prints statistics over all threads, and
+//! `csv::write_stats` writes them for spreadsheets.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # use std::thread;
+//! # fn work(i: u64) -> u64 { i * 2 }
+//! # fn main() {
+//! let handles = (0..4)
+//!     .map(|i| thread::spawn(move || timed!("work", work(i))))
+//!     .collect::<Vec<_>>();
+//! for h in handles {
+//!     h.join().unwrap();
+//! }
+//! # #[cfg(feature = "enabled")] {
+//! let work = tid::registry::stats()
+//!     .into_iter()
+//!     .find(|s| s.label == "work")
+//!     .unwrap();
+//! assert_eq!(work.count, 4);
+//! # }
+//! tid::report();
+//! # }
+//! ```
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use filter;
+use format;
+use histogram::Histograms;
+use sink;
+use stats::Stats;
+
+/// The timings of a single thread.
+struct Shard {
+    thread: u64,
+    histograms: Histograms,
+}
+
+/// The shards of all running threads.
+static SHARDS: Mutex<Vec<Arc<Mutex<Shard>>>> = Mutex::new(Vec::new());
+
+/// The timings of all threads which have exited, added together. Always lock `SHARDS` first.
+static RETIRED: Mutex<Option<Histograms>> = Mutex::new(None);
+
+/// The shard of the current thread, which is retired when the thread exits.
+struct Local(Arc<Mutex<Shard>>);
+
+impl Drop for Local {
+    fn drop(&mut self) {
+        let mut shards = lock(&SHARDS);
+        shards.retain(|shard| !Arc::ptr_eq(shard, &self.0));
+        let shard = lock(&self.0);
+        lock(&RETIRED)
+            .get_or_insert_with(Histograms::default)
+            .merge(&shard.histograms);
+    }
+}
+
+thread_local! {
+    static SHARD: Local = {
+        let shard = Arc::new(Mutex::new(Shard {
+            thread: ::thread_id(),
+            histograms: Histograms::default(),
+        }));
+        lock(&SHARDS).push(shard.clone());
+        Local(shard)
+    };
+}
+
+fn lock<T>(m: &Mutex<T>) -> MutexGuard<'_, T> {
+    m.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record a timing of `ns` nanoseconds for `label` on the current thread.
+pub fn record(label: &str, ns: u64) {
+    with_shard(|h| h.record(label, ns));
+}
+
+/// Run `f` on the histograms of the current thread. Does nothing if the thread is shutting down.
+pub(crate) fn with_shard<F: FnOnce(&mut Histograms)>(f: F) {
+    let _ = SHARD.try_with(|local| f(&mut lock(&local.0).histograms));
+}
+
+/// Statistics for each label, over all threads.
+pub fn stats() -> Vec<Stats> {
+    let shards = lock(&SHARDS);
+    let mut all = lock(&RETIRED).clone().unwrap_or_default();
+    for shard in shards.iter() {
+        all.merge(&lock(shard).histograms);
+    }
+    all.stats()
+}
+
+/// Statistics for each label, for each thread which has recorded anything. Threads are numbered
+/// from 1, in the order they first use `tid`. Threads which have exited are added together, as
+/// thread 0, which comes first.
+pub fn stats_by_thread() -> Vec<(u64, Vec<Stats>)> {
+    let shards = lock(&SHARDS);
+    let retired = lock(&RETIRED).as_ref().map(|h| (0, h.stats()));
+    retired
+        .into_iter()
+        .chain(shards.iter().map(|shard| {
+            let shard = lock(shard);
+            (shard.thread, shard.histograms.stats())
+        }))
+        .filter(|(_, stats)| !stats.is_empty())
+        .collect()
+}
+
+/// Forget everything recorded so far, on all threads.
+pub fn reset() {
+    let shards = lock(&SHARDS);
+    *lock(&RETIRED) = None;
+    for shard in shards.iter() {
+        lock(shard).histograms = Histograms::default();
+    }
+}
+
+/// Print statistics for each label over all threads, with the global format and sink.
+pub fn report() {
+    let sink = sink::global();
+    let mut stats = stats();
+    stats.retain(|s| filter::enabled(&s.label));
+    for line in format::global().stats(&stats) {
+        sink.line(&line);
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+use cpu::CpuTime;
+use csv::{self, Separator};
+use fields::Fields;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use serde_json;
+use stats::Stats;
+use throughput::Throughput;
+
+/// The recorded sections of a `Timer`, as returned by `Timer::report`.
+///
+/// With the `serde` feature the report can be serialized, for instance to JSON with `to_json`:
+///
+/// ```
+/// # use tid::Timer;
+/// let mut t = Timer::new();
+/// t.mark("parse");
+/// t.mark("validate");
+/// let report = t.report();
+/// # #[cfg(feature = "enabled")]
+/// assert_eq!(report.entries[1].label, "validate");
+/// # #[cfg(feature = "serde")]
+/// println!("{}", report.to_json());
+/// ```
+///
+/// which gives
+///
+/// ```text
+/// {"origin_ns":8127459203311,"thread":1,"total_ns":2301,"entries":[{"label":"parse","index":0,"parent":null,"depth":0,"start_ns":0,"duration_ns":1290,"cpu":null,"fields":{},"throughput":null,"budget_ns":null},{"label":"validate","index":1,"parent":null,"depth":0,"start_ns":1290,"duration_ns":1011,"cpu":null,"fields":{},"throughput":null,"budget_ns":null}]}
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Report {
+    /// Clock reading when the `Timer` was created, in nanoseconds.
+    pub origin_ns: u64,
+    /// The thread the `Timer` was created on. Threads are numbered from 1.
+    pub thread: u64,
+    /// Time from the creation of the `Timer` to the last sample, in nanoseconds.
+    pub total_ns: u64,
+    /// All sections, in the order they were started.
+    pub entries: Vec<Entry>,
+}
+
+/// A single section in a `Report`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Entry {
+    pub label: String,
+    /// Position of this entry in `Report::entries`.
+    pub index: usize,
+    /// Index of the section this section is nested in, if any.
+    pub parent: Option<usize>,
+    /// Number of sections this section is nested in.
+    pub depth: usize,
+    /// Start of the section, relative to the creation of the `Timer`.
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    /// CPU time spent in the section, if it was measured.
+    pub cpu: Option<CpuTime>,
+    /// Fields given with `Timer::mark_with`, or `mark!`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fields: Fields,
+    /// Bytes or items processed, given with `Timer::mark_with_throughput`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub throughput: Option<Throughput>,
+    /// The budget of the section, if it has one. See the `budget` module.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub budget_ns: Option<u64>,
+}
+
+impl Entry {
+    /// Did the section take longer than its budget?
+    pub fn is_over_budget(&self) -> bool {
+        self.budget_ns.is_some_and(|b| self.duration_ns > b)
+    }
+}
+
+impl Report {
+    /// Time spent in the entry at `index` which is not spent in any nested section.
+    pub fn self_ns(&self, index: usize) -> u64 {
+        let children = self
+            .entries
+            .iter()
+            .filter(|e| e.parent == Some(index))
+            .map(|e| e.duration_ns)
+            .sum::<u64>();
+        self.entries[index].duration_ns.saturating_sub(children)
+    }
+
+    /// Is any section nested in another?
+    pub fn is_nested(&self) -> bool {
+        self.entries.iter().any(|e| e.parent.is_some())
+    }
+
+    /// Keep only the entries for which `keep` returns true. Entries whose parent is removed are
+    /// moved up to the closest ancestor which is kept.
+    ///
+    /// ```
+    /// # use tid::Timer;
+    /// let mut t = Timer::new();
+    /// t.enter("load");
+    /// t.mark("parse");
+    /// t.exit();
+    /// let report = t.report().filter(|e| e.label != "load");
+    /// # #[cfg(feature = "enabled")] {
+    /// assert_eq!(report.entries.len(), 1);
+    /// assert_eq!((report.entries[0].parent, report.entries[0].depth), (None, 0));
+    /// # }
+    /// ```
+    pub fn filter<F: FnMut(&Entry) -> bool>(&self, mut keep: F) -> Report {
+        let mut moved = vec![None; self.entries.len()];
+        let mut entries: Vec<Entry> = Vec::new();
+        for e in &self.entries {
+            if !keep(e) {
+                continue;
+            }
+            let mut parent = e.parent;
+            while let Some(p) = parent {
+                if moved[p].is_some() {
+                    break;
+                }
+                parent = self.entries[p].parent;
+            }
+            let parent = parent.and_then(|p| moved[p]);
+            moved[e.index] = Some(entries.len());
+            entries.push(Entry {
+                index: entries.len(),
+                parent,
+                depth: parent.map(|p| entries[p].depth + 1).unwrap_or(0),
+                ..e.clone()
+            });
+        }
+        Report {
+            origin_ns: self.origin_ns,
+            thread: self.thread,
+            total_ns: self.total_ns,
+            entries,
+        }
+    }
+
+    /// Statistics for each label, in the order the labels first appear. The throughput is the sum
+    /// over all sections with the label, if all of them have one, and `over_budget` counts the
+    /// sections which went over their budget.
+    pub fn stats(&self) -> Vec<Stats> {
+        let mut index = HashMap::new();
+        let mut labels: Vec<(&str, Vec<&Entry>)> = Vec::new();
+        for e in &self.entries {
+            let i = *index.entry(&e.label[..]).or_insert_with(|| {
+                labels.push((&e.label, Vec::new()));
+                labels.len() - 1
+            });
+            labels[i].1.push(e);
+        }
+        labels
+            .iter()
+            .map(|(label, entries)| {
+                let samples = entries.iter().map(|e| e.duration_ns).collect::<Vec<_>>();
+                Stats {
+                    throughput: Throughput::sum(entries.iter().map(|e| e.throughput)),
+                    over_budget: entries.iter().filter(|e| e.is_over_budget()).count() as u64,
+                    ..Stats::from_samples(label, &samples)
+                }
+            })
+            .collect()
+    }
+
+    /// Is any label used for more than one section?
+    pub fn has_repeats(&self) -> bool {
+        let mut seen = HashSet::new();
+        !self.entries.iter().all(|e| seen.insert(&e.label[..]))
+    }
+
+    /// Write the report as CSV to `w`. See the `csv` module.
+    pub fn write_csv<W: Write>(&self, w: W) -> io::Result<()> {
+        csv::write_report(w, self, Separator::Comma)
+    }
+
+    /// Write the report as TSV to `w`. See the `csv` module.
+    pub fn write_tsv<W: Write>(&self, w: W) -> io::Result<()> {
+        csv::write_report(w, self, Separator::Tab)
+    }
+
+    /// Serialize the report to a JSON string.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a `Report` is always valid JSON")
+    }
+
+    /// Write the report as JSON to `w`.
+    #[cfg(feature = "serde")]
+    pub fn write_json<W: Write>(&self, w: W) -> io::Result<()> {
+        serde_json::to_writer(w, self).map_err(io::Error::from)
+    }
+}
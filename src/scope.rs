@@ -0,0 +1,37 @@
+use {_Start, _start, _timed};
+
+/// Start timing a scope. The time is reported, just like with `timed!`, when the returned guard
+/// is dropped, so early returns and `?` are measured as well.
+///
+/// # Examples
+///
+/// ```
+/// # fn f() {  }
+/// {
+///     let _s = tid::scope("doing f");
+///     f();
+/// } // `_s` is dropped, and "doing f" is reported here.
+/// ```
+///
+/// Note that `let _ = tid::scope(..)` drops the guard right away, so remember to name it.
+pub fn scope(label: &'static str) -> Scope {
+    Scope {
+        label,
+        start: Some(_start()),
+    }
+}
+
+/// Guard returned by `scope`. Reports the time since it was created when dropped.
+#[must_use = "the scope is timed until the guard is dropped"]
+pub struct Scope {
+    label: &'static str,
+    start: Option<_Start>,
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start.take() {
+            _timed(self.label, start);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+//! Where the output of `timed!` and `Timer::present` goes.
+//!
+//! By default everything is printed to `stdout`. Use `tid::set_sink` to change this for the whole
+//! process, or `Timer::set_sink` for a single `Timer`.
+//!
+//! # Examples
+//!
+//! ```
+//! # #[macro_use] extern crate tid;
+//! # use tid::Timer;
+//! # use tid::sink::{Memory, Stderr};
+//! # fn main() {
+//! tid::set_sink(Stderr);
+//! timed!("goes to stderr", 1 + 1);
+//!
+//! let memory = Memory::new();
+//! let mut t = Timer::new();
+//! t.set_sink(memory.clone());
+//! t.mark("kept in memory");
+//! t.present();
+//! # #[cfg(feature = "enabled")]
+//! assert!(memory.lines()[0].contains("kept in memory"));
+//! # }
+//! ```
+use std::io::Write;
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Something that takes lines of output.
+pub trait Sink: Send + Sync {
+    /// Take a single line of output. `line` does not end with a newline.
+    fn line(&self, line: &str);
+}
+
+impl<S: Sink + ?Sized> Sink for Arc<S> {
+    fn line(&self, line: &str) {
+        (**self).line(line)
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn line(&self, line: &str) {
+        (**self).line(line)
+    }
+}
+
+static SINK: RwLock<Option<Arc<dyn Sink>>> = RwLock::new(None);
+
+/// Set the sink used by `timed!`, `scope` and all `Timer`s which don't have their own sink.
+pub fn set_sink<S: Sink + 'static>(sink: S) {
+    *SINK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(sink));
+}
+
+/// Get the sink set with `set_sink`, or `Stdout` if it's not set.
+pub(crate) fn global() -> Arc<dyn Sink> {
+    match *SINK.read().unwrap_or_else(|e| e.into_inner()) {
+        Some(ref sink) => sink.clone(),
+        None => Arc::new(Stdout),
+    }
+}
+
+/// Print lines to `stdout`. This is the default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stdout;
+
+impl Sink for Stdout {
+    fn line(&self, line: &str) {
+        println!("{}", line);
+    }
+}
+
+/// Print lines to `stderr`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stderr;
+
+impl Sink for Stderr {
+    fn line(&self, line: &str) {
+        eprintln!("{}", line);
+    }
+}
+
+/// Write lines to any `io::Write`, like a `File`. Errors are ignored.
+pub struct Writer<W> {
+    inner: Mutex<W>,
+}
+
+impl<W: Write + Send> Writer<W> {
+    pub fn new(w: W) -> Self {
+        Self {
+            inner: Mutex::new(w),
+        }
+    }
+
+    /// Get back the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<W: Write + Send> Sink for Writer<W> {
+    fn line(&self, line: &str) {
+        let mut w = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(w, "{}", line).and_then(|_| w.flush());
+    }
+}
+
+/// Keep all lines in memory. Clones share the same lines, so you can keep a clone around to look
+/// at the lines after passing the sink away.
+#[derive(Debug, Clone, Default)]
+pub struct Memory {
+    lines: Arc<Mutex<Vec<String>>>,
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a copy of all lines so far.
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Take all lines so far, leaving the sink empty.
+    pub fn take(&self) -> Vec<String> {
+        let mut lines = self.lines.lock().unwrap_or_else(|e| e.into_inner());
+        ::std::mem::take(&mut *lines)
+    }
+}
+
+impl Sink for Memory {
+    fn line(&self, line: &str) {
+        self.lines
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(line.to_string());
+    }
+}
+
+/// Throw all lines away.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Null;
+
+impl Sink for Null {
+    fn line(&self, _line: &str) {}
+}
@@ -0,0 +1,120 @@
+//! Statistics over repeated sections with the same label.
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use throughput::Throughput;
+
+/// Statistics for all samples with the same label. All times are in nanoseconds.
+///
+/// ```
+/// # use tid::Stats;
+/// let s = Stats::from_samples("parse", &[4, 1, 3, 2, 10]);
+/// assert_eq!(s.count, 5);
+/// assert_eq!(s.total_ns, 20);
+/// assert_eq!((s.min_ns, s.median_ns, s.max_ns), (1, 3, 10));
+/// assert_eq!(s.mean_ns, 4.0);
+/// assert_eq!(s.p90_ns, 10);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stats {
+    pub label: String,
+    pub count: u64,
+    pub total_ns: u64,
+    pub min_ns: u64,
+    pub max_ns: u64,
+    pub mean_ns: f64,
+    pub median_ns: u64,
+    /// Population standard deviation.
+    pub stddev_ns: f64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    /// Bytes or items processed over all samples, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub throughput: Option<Throughput>,
+    /// The number of samples over their budget, if known.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub over_budget: u64,
+}
+
+impl Stats {
+    /// Compute the statistics of `samples`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `samples` is empty.
+    // `usize::is_multiple_of` is too new for the Rust versions we support.
+    #[allow(clippy::manual_is_multiple_of)]
+    pub fn from_samples(label: &str, samples: &[u64]) -> Stats {
+        assert!(!samples.is_empty(), "no samples for {:?}", label);
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let n = sorted.len();
+        let total = sorted.iter().sum::<u64>();
+        let mean = total as f64 / n as f64;
+        let variance = sorted
+            .iter()
+            .map(|&s| (s as f64 - mean) * (s as f64 - mean))
+            .sum::<f64>()
+            / n as f64;
+        let median = if n % 2 == 0 {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2
+        } else {
+            sorted[n / 2]
+        };
+        Stats {
+            label: label.to_string(),
+            count: n as u64,
+            total_ns: total,
+            min_ns: sorted[0],
+            max_ns: sorted[n - 1],
+            mean_ns: mean,
+            median_ns: median,
+            stddev_ns: variance.sqrt(),
+            p90_ns: percentile(&sorted, 90.0),
+            p99_ns: percentile(&sorted, 99.0),
+            throughput: None,
+            over_budget: 0,
+        }
+    }
+
+    /// Add the samples of `other`, with the same label, to `self`. Count, total, min, max, mean,
+    /// standard deviation, throughput and budget counts stay exact. The samples themselves are
+    /// gone, so the median and percentiles are estimated, as averages weighted by count.
+    ///
+    /// ```
+    /// # use tid::Stats;
+    /// let mut s = Stats::from_samples("parse", &[1, 2, 3]);
+    /// s.merge(&Stats::from_samples("parse", &[4, 10]));
+    /// let all = Stats::from_samples("parse", &[1, 2, 3, 4, 10]);
+    /// assert_eq!((s.count, s.total_ns, s.min_ns, s.max_ns), (5, 20, 1, 10));
+    /// assert_eq!(s.mean_ns, all.mean_ns);
+    /// assert!((s.stddev_ns - all.stddev_ns).abs() < 1e-9);
+    /// ```
+    pub fn merge(&mut self, other: &Stats) {
+        let (a, b) = (self.count as f64, other.count as f64);
+        let n = a + b;
+        // Sums of squares, from the population variances.
+        let squares = |s: &Stats, n: f64| n * (s.stddev_ns * s.stddev_ns + s.mean_ns * s.mean_ns);
+        let squares = squares(self, a) + squares(other, b);
+        let weighted = |x: u64, y: u64| ((x as f64 * a + y as f64 * b) / n).round() as u64;
+
+        self.count += other.count;
+        self.total_ns = self.total_ns.saturating_add(other.total_ns);
+        self.min_ns = self.min_ns.min(other.min_ns);
+        self.max_ns = self.max_ns.max(other.max_ns);
+        self.mean_ns = (self.mean_ns * a + other.mean_ns * b) / n;
+        self.stddev_ns = (squares / n - self.mean_ns * self.mean_ns).max(0.0).sqrt();
+        self.median_ns = weighted(self.median_ns, other.median_ns);
+        self.p90_ns = weighted(self.p90_ns, other.p90_ns);
+        self.p99_ns = weighted(self.p99_ns, other.p99_ns);
+        self.throughput = Throughput::sum(vec![self.throughput, other.throughput]);
+        self.over_budget += other.over_budget;
+    }
+}
+
+/// The nearest-rank percentile `p` of the sorted, non-empty `sorted`.
+pub(crate) fn percentile(sorted: &[u64], p: f64) -> u64 {
+    let rank = (p / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.max(1).min(sorted.len()) - 1]
+}
@@ -0,0 +1,75 @@
+//! How much work a section did, so that `present` can print rates next to the times.
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The amount of work done in a section. See `Timer::mark_with_throughput`.
+///
+/// ```
+/// # use tid::Throughput;
+/// assert_eq!(Throughput::Bytes(412_300).rate(1_000_000), "412.3 MB/s");
+/// assert_eq!(Throughput::Items(1_200).rate(1_000_000), "1.2M items/s");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Throughput {
+    Bytes(u64),
+    Items(u64),
+}
+
+impl Throughput {
+    /// The number of bytes or items.
+    pub fn count(self) -> u64 {
+        match self {
+            Throughput::Bytes(n) | Throughput::Items(n) => n,
+        }
+    }
+
+    /// Bytes or items per second, if done in `ns` nanoseconds.
+    pub fn per_second(self, ns: u64) -> f64 {
+        self.count() as f64 / ns as f64 * 1_000_000_000.0
+    }
+
+    /// The rate if done in `ns` nanoseconds, like `412.3 MB/s` or `1.2M items/s`. Units are powers
+    /// of 1000.
+    pub fn rate(self, ns: u64) -> String {
+        if ns == 0 {
+            return "-".to_string();
+        }
+        match self {
+            Throughput::Bytes(_) => {
+                let (v, unit) = scale(
+                    self.per_second(ns),
+                    &["B/s", "KB/s", "MB/s", "GB/s", "TB/s"],
+                );
+                format!("{:.1} {}", v, unit)
+            }
+            Throughput::Items(_) => {
+                let (v, unit) = scale(self.per_second(ns), &["", "k", "M", "G", "T"]);
+                format!("{:.1}{} items/s", v, unit)
+            }
+        }
+    }
+
+    /// The sum of `all`, or `None` if any is `None` or they're not all of the same kind.
+    pub(crate) fn sum<I: IntoIterator<Item = Option<Throughput>>>(all: I) -> Option<Throughput> {
+        let mut all = all.into_iter();
+        let mut sum = all.next()??;
+        for t in all {
+            sum = match (sum, t?) {
+                (Throughput::Bytes(a), Throughput::Bytes(b)) => Throughput::Bytes(a + b),
+                (Throughput::Items(a), Throughput::Items(b)) => Throughput::Items(a + b),
+                _ => return None,
+            };
+        }
+        Some(sum)
+    }
+}
+
+fn scale(mut v: f64, units: &[&'static str]) -> (f64, &'static str) {
+    let mut i = 0;
+    while v >= 1000.0 && i + 1 < units.len() {
+        v /= 1000.0;
+        i += 1;
+    }
+    (v, units[i])
+}
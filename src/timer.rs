@@ -0,0 +1,572 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+use std::time::Duration;
+
+use budget;
+use clock::{Clock, Monotonic};
+use cpu::CpuTime;
+use fields::{self, Fields, Value};
+use filter;
+use format::{self, Format};
+use histogram::{Histogram, Histograms};
+use registry;
+use report::{Entry, Report};
+use sink::{self, Sink};
+use stats::Stats;
+use throughput::Throughput;
+
+/// A `Timer` is used for timing multiple consecutive sections of your code. The first timing is
+/// done when the object is constructed. The second timing is done at the first call to `mark`.
+/// This time difference will be the one reported with the label you pass to `mark`.
+///
+/// # Examples
+///
+/// ```
+/// # use tid::Timer;
+/// # fn f() {  }
+/// # fn g() {  }
+/// # fn h() {  }
+/// let mut t = Timer::new();
+/// f();
+/// t.mark("Doing f");
+/// g();
+/// t.mark("G is executed");
+/// h();
+/// t.mark("Done with H");
+/// t.present();
+/// ```
+///
+/// When `present` is called, we print all timings:
+///
+/// ```text
+/// [timer] Doing f          0.12004ms
+/// [timer] G is executed   21.98122ms
+/// [timer] Done with H      7.00124ms
+/// ```
+///
+/// Sections can also be nested by using `enter` and `exit`. Calls to `mark` in between are
+/// counted as children of the entered section.
+///
+/// ```
+/// # use tid::Timer;
+/// # fn parse() {  }
+/// # fn validate() {  }
+/// # fn render() {  }
+/// let mut t = Timer::new();
+/// t.enter("load");
+/// parse();
+/// t.mark("parse");
+/// validate();
+/// t.mark("validate");
+/// t.exit();
+/// render();
+/// t.mark("render");
+/// t.present();
+/// ```
+///
+/// Now `present` prints a tree, with the total time, the time not spent in any child section,
+/// and the percentage of the parent section (or the whole timer, for the top level sections):
+///
+/// ```text
+/// [timer] load                         29.0841ms    0.0031ms  80.6%
+/// [timer]   parse                      21.9812ms   21.9812ms  75.6%
+/// [timer]   validate                    7.0998ms    7.0998ms  24.4%
+/// [timer] render                        7.0012ms    7.0012ms  19.4%
+/// ```
+///
+/// Instead of calling `exit` yourself, you can use `section`, which exits when the returned guard
+/// is dropped. The guard can be used as the `Timer` itself, so sections can be nested:
+///
+/// ```
+/// # use tid::Timer;
+/// # fn parse() -> Result<(), ()> { Ok(()) }
+/// fn load(t: &mut Timer) -> Result<(), ()> {
+///     let mut s = t.section("load");
+///     parse()?;
+///     s.mark("parse");
+///     Ok(())
+/// }
+///
+/// let mut t = Timer::new();
+/// load(&mut t).unwrap();
+/// t.present();
+/// ```
+pub struct Timer {
+    times: Vec<u64>,
+    /// CPU time for each sample in `times`, if measured.
+    cpu: Vec<Option<CpuTime>>,
+    measure_cpu: bool,
+    strs: Vec<Cow<'static, str>>,
+    spans: Vec<Span>,
+    /// Sections which are entered, but not yet exited.
+    open: Vec<usize>,
+    /// Index into `times` of the last section boundary.
+    last: usize,
+    thread: u64,
+    sink: Option<Arc<dyn Sink>>,
+    format: Option<Format>,
+    clock: Box<dyn Clock>,
+    /// Where closed sections go, if `use_histograms` is called.
+    histograms: Option<Histograms>,
+    /// Budgets set with `set_budget`, in nanoseconds.
+    budgets: HashMap<String, u64>,
+    violations: u64,
+}
+
+/// Where in `times` a section starts and ends, which section it is nested in, its fields,
+/// throughput and budget.
+#[derive(Clone)]
+struct Span {
+    start: usize,
+    end: usize,
+    parent: Option<usize>,
+    fields: Fields,
+    throughput: Option<Throughput>,
+    budget: Option<u64>,
+}
+
+impl Timer {
+    /// Create a new `Timer`. The first time sample is taken here.
+    pub fn new() -> Self {
+        Self::with_clock(Monotonic)
+    }
+
+    /// Create a new `Timer`, which takes its time samples from `clock`. See the `clock` module.
+    pub fn with_clock<C: Clock + 'static>(clock: C) -> Self {
+        let enabled = ::enabled();
+        let capacity = if enabled { 100 } else { 0 };
+        let mut s = Self {
+            times: Vec::with_capacity(capacity),
+            cpu: Vec::with_capacity(capacity),
+            measure_cpu: false,
+            strs: Vec::with_capacity(capacity),
+            spans: Vec::with_capacity(capacity),
+            open: Vec::new(),
+            last: 0,
+            thread: ::thread_id(),
+            sink: None,
+            format: None,
+            clock: Box::new(clock),
+            histograms: None,
+            budgets: HashMap::new(),
+            violations: 0,
+        };
+        if enabled {
+            s.times.push(s.clock.now());
+            s.cpu.push(None);
+        }
+        s
+    }
+
+    /// Mark off a secion with the given label. The label can be a `&'static str` or a `String`.
+    /// To format a label only when timing is on, use `mark!`.
+    pub fn mark<L: Into<Cow<'static, str>>>(&mut self, label: L) {
+        self.close(label, Fields::new(), None);
+    }
+
+    /// Mark off a section, like `mark`, with fields giving some context. The fields are printed by
+    /// `present` and are part of the `Report`. See also `mark!`.
+    ///
+    /// ```
+    /// # use tid::{Timer, Value};
+    /// let mut t = Timer::new();
+    /// t.mark_with("parse", &[("rows", 1200.into()), ("file", "a.csv".into())]);
+    /// let report = t.report();
+    /// # #[cfg(feature = "enabled")]
+    /// assert_eq!(report.entries[0].fields["rows"], Value::Int(1200));
+    /// t.present();
+    /// ```
+    ///
+    /// prints something like
+    ///
+    /// ```text
+    /// [timer] parse                         0.0004ms file="a.csv" rows=1200
+    /// ```
+    pub fn mark_with<L: Into<Cow<'static, str>>>(&mut self, label: L, fields: &[(&str, Value)]) {
+        if ::enabled() {
+            self.close(label, fields::collect(fields), None);
+        }
+    }
+
+    /// Mark off a section, like `mark`, which processed `throughput` bytes or items. `present`
+    /// prints the rate next to the time.
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use tid::{MockClock, Throughput, Timer};
+    /// # use tid::sink::Memory;
+    /// let clock = MockClock::new();
+    /// let mut t = Timer::with_clock(clock.clone());
+    /// clock.advance(Duration::from_millis(2));
+    /// t.mark_with_throughput("decode", Throughput::Bytes(824_600));
+    /// clock.advance(Duration::from_millis(1));
+    /// t.mark_with_throughput("index", Throughput::Items(1_200));
+    ///
+    /// let memory = Memory::new();
+    /// t.set_sink(memory.clone());
+    /// t.present();
+    /// # #[cfg(feature = "enabled")]
+    /// assert_eq!(
+    ///     memory.lines(),
+    ///     vec![
+    ///         "\t[timer] decode                        2.0000ms    412.3 MB/s",
+    ///         "\t[timer] index                         1.0000ms  1.2M items/s",
+    ///     ]
+    /// );
+    /// ```
+    pub fn mark_with_throughput<L: Into<Cow<'static, str>>>(
+        &mut self,
+        label: L,
+        throughput: Throughput,
+    ) {
+        self.close(label, Fields::new(), Some(throughput));
+    }
+
+    /// Close a section from the last sample to a new one.
+    fn close<L: Into<Cow<'static, str>>>(
+        &mut self,
+        label: L,
+        fields: Fields,
+        throughput: Option<Throughput>,
+    ) {
+        if !::enabled() {
+            return;
+        }
+        let start = self.last;
+        let end = self.sample();
+        let index = self.push(label, start, end);
+        self.spans[index].fields = fields;
+        self.spans[index].throughput = throughput;
+        self.check_budget(index);
+        self.fold();
+    }
+
+    /// Open a new section with the given label. All sections marked off before the matching call
+    /// to `exit` are nested inside it.
+    pub fn enter<L: Into<Cow<'static, str>>>(&mut self, label: L) {
+        if !::enabled() {
+            return;
+        }
+        let start = self.sample();
+        let index = self.push(label, start, start);
+        self.open.push(index);
+    }
+
+    /// Close the section last opened with `enter`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no open sections.
+    pub fn exit(&mut self) {
+        if !::enabled() {
+            return;
+        }
+        let index = self
+            .open
+            .pop()
+            .expect("`Timer::exit` called without a matching `Timer::enter`");
+        self.spans[index].end = self.sample();
+        self.check_budget(index);
+        self.fold();
+    }
+
+    /// Open a new section with the given label, like `enter`. The section is closed when the
+    /// returned guard is dropped.
+    pub fn section<L: Into<Cow<'static, str>>>(&mut self, label: L) -> Section<'_> {
+        let index = self.spans.len();
+        self.enter(label);
+        Section { timer: self, index }
+    }
+
+    /// Set the budget of sections with `label`, for this `Timer` only. Sections over their budget
+    /// are flagged by `present` and counted in `violations`. See the `budget` module.
+    pub fn set_budget<L: Into<String>>(&mut self, label: L, budget: Duration) {
+        self.budgets.insert(label.into(), ::nanos(budget));
+    }
+
+    /// The number of sections which went over their budget.
+    pub fn violations(&self) -> u64 {
+        self.violations
+    }
+
+    /// Look up the budget of the just closed section at `index`, and count it if it's over.
+    fn check_budget(&mut self, index: usize) {
+        let label = &self.strs[index];
+        let budget = match self.budgets.get(&label[..]) {
+            Some(&budget) => budget,
+            None => match budget::lookup(label) {
+                Some(budget) => budget,
+                None => return,
+            },
+        };
+        let span = &self.spans[index];
+        let duration = self.times[span.end] - self.times[span.start];
+        if budget::check(label, duration, budget) {
+            self.violations += 1;
+        }
+        self.spans[index].budget = Some(budget);
+    }
+
+    /// Send the output of `present` to `sink`, instead of the one set with `tid::set_sink`.
+    pub fn set_sink<S: Sink + 'static>(&mut self, sink: S) {
+        self.sink = Some(Arc::new(sink));
+    }
+
+    /// Print with `format` in `present`, instead of the one set with `tid::set_format`.
+    pub fn set_format(&mut self, format: Format) {
+        self.format = Some(format);
+    }
+
+    /// Start or stop measuring CPU time next to wall time. Only sections which start and end while
+    /// this is on get a CPU time. See the `cpu` module.
+    pub fn measure_cpu(&mut self, on: bool) {
+        self.measure_cpu = on;
+        if on && ::enabled() && self.cpu[self.last].is_none() {
+            self.cpu[self.last] = CpuTime::now();
+        }
+    }
+
+    /// Record sections into one fixed size histogram for each label, instead of keeping every
+    /// section. Use this when marking the same labels very many times. Sections recorded so far
+    /// are moved into the histograms. See the `histogram` module.
+    ///
+    /// After this, `report` only has the sections which are still open, and `present` always
+    /// prints statistics.
+    pub fn use_histograms(&mut self) {
+        if self.histograms.is_none() {
+            self.histograms = Some(Histograms::default());
+            self.fold();
+        }
+    }
+
+    /// The histogram for `label`, if `use_histograms` is called and `label` is recorded.
+    pub fn histogram(&self, label: &str) -> Option<&Histogram> {
+        self.histograms
+            .as_ref()?
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, h)| h)
+    }
+
+    /// Add all sections of `other` to the histograms of `self`, calling `use_histograms` first.
+    ///
+    /// ```
+    /// # use tid::Timer;
+    /// let mut a = Timer::new();
+    /// a.use_histograms();
+    /// a.mark("parse");
+    /// let mut b = Timer::new();
+    /// b.mark("parse");
+    /// b.mark("render");
+    /// a.merge(&b);
+    /// # #[cfg(feature = "enabled")] {
+    /// assert_eq!(a.histogram("parse").unwrap().count(), 2);
+    /// assert_eq!(a.histogram("render").unwrap().count(), 1);
+    /// # }
+    /// ```
+    pub fn merge(&mut self, other: &Timer) {
+        self.use_histograms();
+        let histograms = self.histograms.as_mut().unwrap();
+        if let Some(ref h) = other.histograms {
+            histograms.merge(h);
+        }
+        for e in other.report().entries {
+            histograms.record(&e.label, e.duration_ns);
+        }
+    }
+
+    /// Record all sections in the global registry, so they're part of `tid::report`. See the
+    /// `registry` module.
+    pub fn submit(&self) {
+        let entries = self.report().entries;
+        registry::with_shard(|shard| {
+            if let Some(ref h) = self.histograms {
+                shard.merge(h);
+            }
+            for e in &entries {
+                shard.record(&e.label, e.duration_ns);
+            }
+        });
+    }
+
+    /// Move all sections into the histograms, if in use and no sections are open. Only the last
+    /// sample is kept, as the start of the next section.
+    fn fold(&mut self) {
+        if !::enabled() || !self.open.is_empty() {
+            return;
+        }
+        let histograms = match self.histograms {
+            Some(ref mut h) => h,
+            None => return,
+        };
+        for (span, label) in self.spans.iter().zip(self.strs.iter()) {
+            histograms.record(label, self.times[span.end] - self.times[span.start]);
+        }
+        self.spans.clear();
+        self.strs.clear();
+        let (time, cpu) = (self.times[self.last], self.cpu[self.last]);
+        self.times.clear();
+        self.times.push(time);
+        self.cpu.clear();
+        self.cpu.push(cpu);
+        self.last = 0;
+    }
+
+    /// Take a new time sample, and make it the start of the next section.
+    fn sample(&mut self) -> usize {
+        self.times.push(self.clock.now());
+        self.cpu.push(if self.measure_cpu {
+            CpuTime::now()
+        } else {
+            None
+        });
+        self.last = self.times.len() - 1;
+        self.last
+    }
+
+    fn push<L: Into<Cow<'static, str>>>(&mut self, label: L, start: usize, end: usize) -> usize {
+        let parent = self.open.last().cloned();
+        self.spans.push(Span {
+            start,
+            end,
+            parent,
+            fields: Fields::new(),
+            throughput: None,
+            budget: None,
+        });
+        self.strs.push(label.into());
+        self.spans.len() - 1
+    }
+
+    /// Get the timings recorded so far. Sections which are still open end at the last sample.
+    pub fn report(&self) -> Report {
+        let origin = match self.times.first() {
+            Some(&origin) => origin,
+            None => {
+                return Report {
+                    origin_ns: 0,
+                    thread: self.thread,
+                    total_ns: 0,
+                    entries: Vec::new(),
+                }
+            }
+        };
+        let mut depths = Vec::with_capacity(self.spans.len());
+        let mut entries = Vec::with_capacity(self.spans.len());
+        for (index, (span, label)) in self.spans.iter().zip(self.strs.iter()).enumerate() {
+            let depth = span.parent.map(|p| depths[p] + 1).unwrap_or(0);
+            depths.push(depth);
+            let end = if self.open.contains(&index) {
+                self.last
+            } else {
+                span.end
+            };
+            entries.push(Entry {
+                label: label.to_string(),
+                index,
+                parent: span.parent,
+                depth,
+                start_ns: self.times[span.start] - origin,
+                duration_ns: self.times[end] - self.times[span.start],
+                cpu: match (self.cpu[span.start], self.cpu[end]) {
+                    (Some(c0), Some(c1)) => Some(c1.since(&c0)),
+                    _ => None,
+                },
+                fields: span.fields.clone(),
+                throughput: span.throughput,
+                budget_ns: span.budget,
+            });
+        }
+        Report {
+            origin_ns: origin,
+            thread: self.thread,
+            total_ns: self.times[self.times.len() - 1] - origin,
+            entries,
+        }
+    }
+
+    /// Statistics for each label, in the order the labels first appear. See `Report::stats`.
+    pub fn stats(&self) -> Vec<Stats> {
+        match self.histograms {
+            Some(ref h) => {
+                let mut h = h.clone();
+                for e in self.report().entries {
+                    h.record(&e.label, e.duration_ns);
+                }
+                h.stats()
+            }
+            None => self.report().stats(),
+        }
+    }
+
+    /// Print out the timings to the sink, which is `stdout` unless set otherwise. Sections which
+    /// are still open are closed first.
+    ///
+    /// If a label is used for more than one section, like when marking inside a loop, one line of
+    /// statistics is printed for each label instead. See `Format::listing`.
+    ///
+    /// Only sections whose labels are picked by the `TID` environment variable are printed. See
+    /// the `filter` module.
+    pub fn present(mut self) {
+        if !::enabled() {
+            return;
+        }
+        while !self.open.is_empty() {
+            self.exit();
+        }
+        let sink = self.sink.take().unwrap_or_else(sink::global);
+        let format = self.format.take().unwrap_or_else(format::global);
+        let lines = match self.histograms {
+            Some(ref h) => {
+                let mut stats = h.stats();
+                stats.retain(|s| filter::enabled(&s.label));
+                format.stats(&stats)
+            }
+            None => format.report(&self.report().filter(|e| filter::enabled(&e.label))),
+        };
+        for line in lines {
+            sink.line(&line);
+        }
+    }
+}
+
+impl Default for Timer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Guard returned by `Timer::section`. Closes the section when dropped.
+#[must_use = "the section is closed when the guard is dropped"]
+pub struct Section<'a> {
+    timer: &'a mut Timer,
+    index: usize,
+}
+
+impl<'a> Deref for Section<'a> {
+    type Target = Timer;
+
+    fn deref(&self) -> &Timer {
+        self.timer
+    }
+}
+
+impl<'a> DerefMut for Section<'a> {
+    fn deref_mut(&mut self) -> &mut Timer {
+        self.timer
+    }
+}
+
+impl<'a> Drop for Section<'a> {
+    fn drop(&mut self) {
+        // Sections entered through the guard but never exited are closed along with it.
+        if self.timer.open.contains(&self.index) {
+            while self.timer.open.last() != Some(&self.index) {
+                self.timer.exit();
+            }
+            self.timer.exit();
+        }
+    }
+}
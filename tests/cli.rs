@@ -0,0 +1,251 @@
+//! Runs the `tid` binary on small files. Run with `cargo test --features cli --test cli`.
+#![cfg(feature = "cli")]
+extern crate serde_json;
+extern crate tid;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use tid::baseline::Snapshot;
+
+/// A fresh directory for the files of the test `name`.
+fn dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("tid-cli-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn tid(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tid"))
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Run `tid`, and return its output, failing if it fails.
+fn ok(dir: &Path, args: &[&str]) -> String {
+    let out = tid(dir, args);
+    assert!(
+        out.status.success(),
+        "tid {:?}: {}",
+        args,
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+fn read(dir: &Path, file: &str) -> String {
+    fs::read_to_string(dir.join(file)).unwrap()
+}
+
+const TRACE: &str = r#"{"traceEvents":[
+    {"name":"main","ph":"X","ts":1000.0,"dur":50.0,"pid":1,"tid":1},
+    {"name":"work","ph":"X","ts":1010.0,"dur":20.0,"pid":1,"tid":2,
+     "args":{"rows":12,"file":"a, b.csv","ok":true,"ratio":0.5}},
+    {"name":"work","ph":"X","ts":1040.0,"dur":5.0,"pid":1,"tid":2},
+    {"name":"ignored","ph":"B","ts":1000.0,"pid":1,"tid":1}
+]}"#;
+
+#[test]
+fn traces_keep_their_threads_and_args() {
+    let dir = dir("trace");
+    fs::write(dir.join("trace.json"), TRACE).unwrap();
+    ok(&dir, &["convert", "trace.json", "out.csv"]);
+    assert_eq!(
+        read(&dir, "out.csv"),
+        "label,index,start_ns,duration_ns,thread,file,ok,ratio,rows\n\
+         main,0,0,50000,1,,,,\n\
+         work,0,10000,20000,2,\"a, b.csv\",true,0.5,12\n\
+         work,1,40000,5000,2,,,,\n"
+    );
+
+    let show = ok(&dir, &["show", "out.csv"]);
+    assert!(show.contains("[thread 1]"));
+    assert!(show.contains("[thread 2]"));
+
+    ok(&dir, &["convert", "out.csv", "back.trace"]);
+    let back: serde_json::Value = serde_json::from_str(&read(&dir, "back.trace")).unwrap();
+    let events = back["traceEvents"].as_array().unwrap();
+    let threads = events.iter().map(|e| e["tid"].as_u64()).collect::<Vec<_>>();
+    assert_eq!(threads, vec![Some(1), Some(2), Some(2)]);
+    assert_eq!(events[1]["args"]["rows"], 12);
+    assert_eq!(events[1]["args"]["file"], "a, b.csv");
+}
+
+#[test]
+fn tables_round_trip() {
+    let dir = dir("table");
+    fs::write(
+        dir.join("in.csv"),
+        "label,index,start_ns,duration_ns,thread,field.label,note\n\
+         \"say \"\"hi\"\", all\",0,0,10,1,first,\n\
+         plain,1,10,20,1,,\"with\ttab\"\n\
+         other,0,5,7,3,,x\n",
+    )
+    .unwrap();
+    // Short sections are printed in nanoseconds, not as zero milliseconds.
+    assert!(ok(&dir, &["show", "in.csv"]).contains("10.0000ns"));
+
+    ok(&dir, &["convert", "in.csv", "a.tsv"]);
+    assert_eq!(
+        read(&dir, "a.tsv"),
+        "label\tindex\tstart_ns\tduration_ns\tthread\tfield.label\tnote\n\
+         say \"hi\", all\t0\t0\t10\t1\tfirst\t\n\
+         plain\t1\t10\t20\t1\t\twith tab\n\
+         other\t0\t5\t7\t3\t\tx\n"
+    );
+
+    // Two threads go to JSON as a list of snapshots.
+    ok(&dir, &["convert", "a.tsv", "b.json"]);
+    let snapshots: Vec<Snapshot> = serde_json::from_str(&read(&dir, "b.json")).unwrap();
+    assert_eq!(snapshots.len(), 2);
+    let first = &snapshots[0].report.entries[0];
+    assert_eq!(first.label, "say \"hi\", all");
+    assert_eq!(first.fields["label"], tid::Value::Str("first".to_string()));
+
+    ok(&dir, &["convert", "b.json", "c.csv"]);
+    ok(&dir, &["convert", "c.csv", "d.tsv"]);
+    assert_eq!(read(&dir, "d.tsv"), read(&dir, "a.tsv"));
+}
+
+#[test]
+fn reports_with_bad_nesting_are_rejected() {
+    let dir = dir("nesting");
+    let entry = |index: usize, parent: &str, depth: usize| {
+        format!(
+            r#"{{"label":"x","index":{},"parent":{},"depth":{},"start_ns":0,"duration_ns":1,"cpu":null}}"#,
+            index, parent, depth
+        )
+    };
+    let report = |entries: &[String]| {
+        format!(
+            r#"{{"origin_ns":0,"thread":1,"total_ns":1,"entries":[{}]}}"#,
+            entries.join(",")
+        )
+    };
+    let cases = [
+        (report(&[entry(0, "null", 0), entry(1, "5", 1)]), "parent 5"),
+        (report(&[entry(0, "1", 1), entry(1, "null", 0)]), "parent 1"),
+        (
+            report(&[entry(0, "null", 0), entry(2, "null", 0)]),
+            "index 2",
+        ),
+        (report(&[entry(0, "null", 0), entry(1, "0", 3)]), "depth 3"),
+    ];
+    for (i, &(ref json, message)) in cases.iter().enumerate() {
+        let file = format!("bad{}.json", i);
+        fs::write(dir.join(&file), json).unwrap();
+        let out = tid(&dir, &["show", &file]);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(1), "{}", stderr);
+        assert!(stderr.contains(message), "{}", stderr);
+    }
+
+    fs::write(
+        dir.join("good.json"),
+        report(&[entry(0, "null", 0), entry(1, "0", 1), entry(2, "1", 2)]),
+    )
+    .unwrap();
+    ok(&dir, &["show", "good.json"]);
+}
+
+#[test]
+fn merge_keeps_statistics_without_sections() {
+    let dir = dir("merge");
+    let stats = tid::Stats::from_samples("parse", &[10, 30]);
+    let only_stats = Snapshot {
+        version: tid::baseline::VERSION,
+        report: tid::Report {
+            origin_ns: 0,
+            thread: 1,
+            total_ns: 0,
+            entries: Vec::new(),
+        },
+        stats: vec![stats],
+    };
+    only_stats.save(dir.join("stats.json")).unwrap();
+    fs::write(
+        dir.join("sections.csv"),
+        "label,index,start_ns,duration_ns,thread\n\
+         parse,0,0,20,1\n\
+         parse,1,20,40,2\n\
+         load,2,60,5,2\n",
+    )
+    .unwrap();
+
+    ok(
+        &dir,
+        &[
+            "merge",
+            "stats.json",
+            "sections.csv",
+            "--output",
+            "all.json",
+        ],
+    );
+    let merged = Snapshot::load(dir.join("all.json")).unwrap();
+    let parse = merged.stats.iter().find(|s| s.label == "parse").unwrap();
+    assert_eq!(
+        (parse.count, parse.total_ns, parse.min_ns, parse.max_ns),
+        (4, 100, 10, 40)
+    );
+    assert_eq!(parse.mean_ns, 25.0);
+    let load = merged.stats.iter().find(|s| s.label == "load").unwrap();
+    assert_eq!((load.count, load.median_ns), (1, 5));
+
+    ok(
+        &dir,
+        &["merge", "stats.json", "sections.csv", "--output", "all.csv"],
+    );
+    let table = read(&dir, "all.csv");
+    let lines = table.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], tid::csv::STATS_COLUMNS.join(","));
+    assert!(lines[1].starts_with("parse,,4,100,10,40,25,"), "{}", table);
+    assert!(lines[2].starts_with("load,,1,5,5,5,5,5,"), "{}", table);
+    ok(&dir, &["merge", "sections.csv", "--output", "all.tsv"]);
+    assert!(read(&dir, "all.tsv").contains("\nparse\t\t2\t60\t20\t40\t30\t"));
+
+    // Traces have no statistics.
+    let out = tid(&dir, &["merge", "sections.csv", "--output", "all.trace"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(!dir.join("all.trace").exists());
+
+    // Both threads of the CSV file together, with exact percentiles.
+    ok(&dir, &["merge", "sections.csv", "--output", "threads.json"]);
+    let merged = Snapshot::load(dir.join("threads.json")).unwrap();
+    let parse = merged.stats.iter().find(|s| s.label == "parse").unwrap();
+    assert_eq!((parse.count, parse.median_ns, parse.p90_ns), (2, 30, 40));
+}
+
+#[test]
+fn diff_fails_on_regressions() {
+    let dir = dir("diff");
+    let table = |durations: &[u64]| {
+        let mut table = "label,index,start_ns,duration_ns,thread\n".to_string();
+        for (i, d) in durations.iter().enumerate() {
+            table += &format!("step,{},0,{},{}\n", i, d, i % 2 + 1);
+        }
+        table
+    };
+    fs::write(dir.join("before.csv"), table(&[100, 101, 99, 100, 102, 98])).unwrap();
+    fs::write(
+        dir.join("after.csv"),
+        table(&[150, 151, 149, 150, 152, 148]),
+    )
+    .unwrap();
+    let out = ok(&dir, &["diff", "before.csv", "after.csv"]);
+    assert!(out.contains("+50.0%"), "{}", out);
+    let out = tid(
+        &dir,
+        &["diff", "before.csv", "after.csv", "--threshold", "10"],
+    );
+    assert_eq!(out.status.code(), Some(1));
+    ok(
+        &dir,
+        &["diff", "before.csv", "after.csv", "--threshold", "60"],
+    );
+}
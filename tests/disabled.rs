@@ -0,0 +1,77 @@
+//! Checks that nothing is timed without the `enabled` feature. Run with
+//! `cargo test --no-default-features --test disabled`.
+#![cfg(not(feature = "enabled"))]
+#[macro_use]
+extern crate tid;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tid::{Clock, Timer};
+
+// A `const` can't call any functions, so these only compile if `timed!` expands to no clock
+// reads, or anything else.
+const SUM: u32 = timed!("sum", 1 + 2);
+const FIELDS: u32 = timed!("fields"; n = 2; 1 + 2);
+
+const fn statements() -> u32 {
+    timed!("statements",
+        let a = 1;
+        let b = 2;
+    );
+    a + b
+}
+
+#[test]
+fn timed_is_just_the_block() {
+    assert_eq!(SUM, 3);
+    assert_eq!(FIELDS, 3);
+    assert_eq!(statements(), 3);
+}
+
+// Labels and fields aren't evaluated, but variables used only there still count as used.
+#[test]
+#[deny(unused_variables)]
+fn labels_and_fields_are_used() {
+    let label = "parse";
+    let rows = [1, 2, 3];
+    assert_eq!(timed!(label, 1 + 1), 2);
+    assert_eq!(timed!("load"; rows = rows.len(); 3), 3);
+    let name = format!("chunk {}", 1);
+    let file = "a.csv";
+    timed!("stmts"; file = file;
+        let n = 4;
+    );
+    timed!(name,
+        let m = n;
+    );
+    assert_eq!(m, 4);
+}
+
+/// A clock which counts how many times it is read.
+#[derive(Clone, Default)]
+struct Counting(Arc<AtomicUsize>);
+
+impl Clock for Counting {
+    fn now(&self) -> u64 {
+        self.0.fetch_add(1, Ordering::SeqCst) as u64
+    }
+}
+
+#[test]
+fn timer_reads_no_clock() {
+    let clock = Counting::default();
+    let mut t = Timer::with_clock(clock.clone());
+    t.mark("a");
+    mark!(t, "chunk {}", 1);
+    mark!(t, "rows"; rows = 12);
+    t.enter("b");
+    {
+        let mut s = t.section("c");
+        s.mark("d");
+    }
+    t.exit();
+    assert!(t.report().entries.is_empty());
+    t.present();
+    assert_eq!(clock.0.load(Ordering::SeqCst), 0);
+}
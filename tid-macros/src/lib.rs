@@ -0,0 +1,52 @@
+//! Attribute macros for `tid`. Use them through `tid::attr`, not from this crate directly.
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Group, TokenStream, TokenTree};
+
+/// Time every call to a function. See `tid::attr::timed`.
+#[proc_macro_attribute]
+pub fn timed(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut tokens = item.into_iter().collect::<Vec<_>>();
+
+    let is_fn = tokens
+        .iter()
+        .zip(tokens.iter().skip(1))
+        .any(|(a, b)| match (a, b) {
+            (TokenTree::Ident(f), TokenTree::Ident(_)) => f.to_string() == "fn",
+            _ => false,
+        });
+    if !is_fn {
+        return error("`#[timed]` can only be used on functions");
+    }
+
+    let attr = attr.into_iter().collect::<Vec<_>>();
+    // The attribute only sees the function, not the `impl` around it, so the default label comes
+    // from the type name of a function defined inside it, which has the type of methods as well.
+    let label = match attr.len() {
+        0 => "{ fn __tid_f() {} ::tid::_function_name(__tid_f) }".to_string(),
+        1 => match attr[0] {
+            TokenTree::Literal(ref l) if l.to_string().starts_with('"') => l.to_string(),
+            _ => return error("expected a string literal label, like `#[timed(\"label\")]`"),
+        },
+        _ => return error("expected a string literal label, like `#[timed(\"label\")]`"),
+    };
+
+    let body = match tokens.pop() {
+        Some(TokenTree::Group(ref g)) if g.delimiter() == Delimiter::Brace => g.clone(),
+        _ => return error("`#[timed]` needs a function with a body"),
+    };
+    // Put the guard in front of the body, so that it's dropped, and the time reported, on every
+    // way out of the function. For `async fn`s this is also where the future is polled.
+    let mut stream = format!("let __tid_scope = ::tid::scope({});", label)
+        .parse::<TokenStream>()
+        .unwrap();
+    stream.extend(body.stream());
+    let mut group = Group::new(Delimiter::Brace, stream);
+    group.set_span(body.span());
+    tokens.push(TokenTree::Group(group));
+    tokens.into_iter().collect()
+}
+
+fn error(msg: &str) -> TokenStream {
+    format!("compile_error!({:?});", msg).parse().unwrap()
+}